@@ -1,6 +1,6 @@
 #!/bin/bash
 
-export SEARCH_BINARY_ENABLED=false
+export SEARCH_STRATEGY=flat
 export SEARCH_INITIAL_ARRAY_SIZE=511
 export SEARCH_MIN_AMOUNT=22
 export SEARCH_TO_FLAT_THRESHOLD=31
@@ -8,7 +8,6 @@ export SEARCH_FLAT_SIZE=3
 
 export DIGGER_MIN_DEPTH=3
 export DIGGER_MAX_DEPTH=10
-export DIGGER_MIN_DEPTH_PROBABILITY=100
 
 export ATTORNEY_LICENSE_MIN_COST=1
 export ATTORNEY_LICENSE_MAX_COST=1
@@ -35,11 +34,12 @@ export EXPLORE_PHASE2_RPS=1
 export ACCOUNTANT_PHASE2_RPS=200
 export DIGGER_PHASE2_RPS=1
 export ATTORNEY_PHASE2_RPS=1
+export ACCOUNTANT_DRAIN_RPS=200
 
 export AREA_CHAN_CAP=5
 export TILE_CHAN_CAP=5
-export LICENSE_CHAN_CAP=30
-export EMPTY_LICENSE_CHAN_CAP=30
+export DIG_QUEUE_CAP=200
+export LICENSE_ACTIVE_LIMIT=30
 export TREASURE_CHAN_CAP=40000
 
 cargo build --release
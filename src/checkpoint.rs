@@ -0,0 +1,184 @@
+use crate::journal::Journal;
+use crate::model::{Area, Tile};
+use std::collections::{BTreeMap, BTreeSet};
+use surf::http::convert::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum CheckpointEvent {
+    // a point tile with treasures handed to the diggers
+    Found { tile: Tile },
+    // a seed area searched to the end, its points are found before it
+    Searched { area: Area },
+    // the point is dug down to `depth`, `left` treasures are still in it
+    Dug {
+        pos_x: u64,
+        pos_y: u64,
+        depth: u64,
+        left: u64,
+    },
+}
+
+// a point tile left to dig, `depth` is the deepest level already dug
+pub struct PendingPoint {
+    pub tile: Tile,
+    pub depth: u64,
+}
+
+pub struct Resumed {
+    // (pos_x, pos_y, size_x, size_y) of searched seed areas
+    pub searched: BTreeSet<(u64, u64, u64, u64)>,
+    pub points: Vec<PendingPoint>,
+}
+
+impl Resumed {
+    pub fn is_searched(&self, area: &Area) -> bool {
+        self.searched
+            .contains(&(area.pos_x, area.pos_y, area.size_x, area.size_y))
+    }
+}
+
+// exploration progress: searched seed areas and the points still to dig,
+// compacted when it is opened like the wal
+pub struct Checkpoint {
+    journal: Journal<CheckpointEvent>,
+}
+
+impl Checkpoint {
+    pub fn open(path: &str, max_depth: u64) -> Result<(Checkpoint, Resumed), std::io::Error> {
+        let (journal, resumed) =
+            Journal::open(path, |events| resume(events, max_depth), compact)?;
+        Ok((Checkpoint { journal }, resumed))
+    }
+
+    pub fn append(&self, event: &CheckpointEvent) {
+        self.journal.append(event)
+    }
+}
+
+fn resume(events: Vec<CheckpointEvent>, max_depth: u64) -> Resumed {
+    let mut searched = BTreeSet::new();
+    // (pos_x, pos_y) -> (treasures left, depth dug)
+    let mut points: BTreeMap<(u64, u64), (u64, u64)> = BTreeMap::new();
+    for event in events {
+        match event {
+            CheckpointEvent::Found { tile } => {
+                points
+                    .entry((tile.area.pos_x, tile.area.pos_y))
+                    .or_insert((tile.amount, 0));
+            }
+            CheckpointEvent::Searched { area } => {
+                searched.insert((area.pos_x, area.pos_y, area.size_x, area.size_y));
+            }
+            CheckpointEvent::Dug {
+                pos_x,
+                pos_y,
+                depth,
+                left,
+            } => {
+                if let Some(point) = points.get_mut(&(pos_x, pos_y)) {
+                    if depth >= point.1 {
+                        *point = (left, depth);
+                    }
+                }
+            }
+        }
+    }
+    Resumed {
+        searched,
+        points: points
+            .into_iter()
+            .filter(|(_, (left, depth))| *left > 0 && *depth < max_depth)
+            .map(|((pos_x, pos_y), (left, depth))| PendingPoint {
+                tile: Tile {
+                    amount: left,
+                    area: Area::new(pos_x, pos_y, 1, 1),
+                },
+                depth,
+            })
+            .collect(),
+    }
+}
+
+fn compact(resumed: &Resumed) -> Vec<CheckpointEvent> {
+    let mut events = vec![];
+    for point in resumed.points.iter() {
+        let area = &point.tile.area;
+        events.push(CheckpointEvent::Found {
+            tile: Tile {
+                amount: point.tile.amount,
+                area: area.clone(),
+            },
+        });
+        if point.depth > 0 {
+            events.push(CheckpointEvent::Dug {
+                pos_x: area.pos_x,
+                pos_y: area.pos_y,
+                depth: point.depth,
+                left: point.tile.amount,
+            });
+        }
+    }
+    for (pos_x, pos_y, size_x, size_y) in resumed.searched.iter() {
+        events.push(CheckpointEvent::Searched {
+            area: Area::new(*pos_x, *pos_y, *size_x, *size_y),
+        });
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checkpoint::{compact, resume, CheckpointEvent, Resumed};
+    use crate::model::{Area, Tile};
+
+    fn found(pos_x: u64, amount: u64) -> CheckpointEvent {
+        let mut tile = Tile::new(pos_x, 0, 1, 1);
+        tile.amount = amount;
+        CheckpointEvent::Found { tile }
+    }
+
+    fn dug(pos_x: u64, depth: u64, left: u64) -> CheckpointEvent {
+        CheckpointEvent::Dug {
+            pos_x,
+            pos_y: 0,
+            depth,
+            left,
+        }
+    }
+
+    fn check(resumed: &Resumed) {
+        assert!(resumed.is_searched(&Area::new(0, 0, 5, 1)));
+        assert!(!resumed.is_searched(&Area::new(0, 1, 5, 1)));
+        // point 2 is dug out, point 3 reached max depth
+        let points: Vec<(u64, u64, u64)> = resumed
+            .points
+            .iter()
+            .map(|p| (p.tile.area.pos_x, p.tile.amount, p.depth))
+            .collect();
+        assert_eq!(points, vec![(1, 1, 3), (4, 1, 0), (5, 1, 0)]);
+    }
+
+    #[test]
+    fn test_checkpoint_resume() {
+        let resumed = resume(
+            vec![
+                found(1, 2),
+                found(2, 1),
+                found(3, 3),
+                found(4, 1),
+                CheckpointEvent::Searched {
+                    area: Area::new(0, 0, 5, 1),
+                },
+                dug(1, 3, 1),
+                dug(2, 2, 0),
+                dug(3, 10, 1),
+                found(5, 1),
+            ],
+            10,
+        );
+        check(&resumed);
+        // the compacted checkpoint resumes the same progress
+        check(&resume(compact(&resumed), 10));
+    }
+}
@@ -0,0 +1,257 @@
+use crate::config::Config;
+use crate::context::{GameStage, Metrics, SyncContext};
+use crate::http::{http_get, http_post, ApiError};
+use crate::model::{Area, Balance, Dig, License, MoneyList, Tile, TreasureList};
+use crate::retry::RetryPolicy;
+use async_std::task;
+use async_trait::async_trait;
+use std::time::Duration;
+use surf::http::convert::Serialize;
+use url::Url;
+
+// the game api as the workers see it
+#[async_trait]
+pub trait Game: Send + Sync {
+    async fn explore(&self, area: Area) -> Result<Tile, ApiError>;
+    async fn dig(&self, dig: Dig) -> Result<TreasureList, ApiError>;
+    async fn issue_license(&self, coins: MoneyList) -> Result<License, ApiError>;
+    async fn cash(&self, treasure: String) -> Result<MoneyList, ApiError>;
+    async fn balance(&self) -> Result<Balance, ApiError>;
+    async fn health(&self) -> Result<(), ApiError>;
+}
+
+// one http client shared by every worker. each endpoint waits on its own
+// rate limiter, sends its metrics and retries by its own policy until the
+// game is over; explores and licenses stop retrying once it drains
+pub struct GameClient {
+    client: surf::Client,
+    explore_url: Url,
+    dig_url: Url,
+    licenses_url: Url,
+    cash_url: Url,
+    balance_url: Url,
+    health_url: Url,
+    timeout: Duration,
+    license_timeout: Duration,
+    cash_timeout: Duration,
+    health_timeout: Duration,
+    explore_retry: RetryPolicy,
+    dig_retry: RetryPolicy,
+    license_retry: RetryPolicy,
+    cash_retry: RetryPolicy,
+    sync: SyncContext,
+}
+
+impl GameClient {
+    pub fn new(c: &Config, sync: SyncContext) -> GameClient {
+        GameClient {
+            client: surf::Client::new(),
+            explore_url: c.get_explore_url(),
+            dig_url: c.get_dig_url(),
+            licenses_url: c.get_licenses_url(),
+            cash_url: c.get_cash_url(),
+            balance_url: c.get_balance_url(),
+            health_url: c.get_health_check_url(),
+            timeout: Duration::from_millis(c.http_timeout_ms),
+            license_timeout: Duration::from_millis(c.attorney_http_timeout_ms),
+            cash_timeout: Duration::from_millis(c.accountant_http_timeout_ms),
+            health_timeout: Duration::from_millis(c.health_check_timeout_ms),
+            explore_retry: c.retry_explore,
+            dig_retry: c.retry_dig,
+            license_retry: c.retry_licenses,
+            cash_retry: c.retry_cash,
+            sync,
+        }
+    }
+
+    // waits out the backoff and tells whether to send the request again,
+    // requests out of attempts go to the dead letters
+    async fn retry<P: Serialize + Sync>(
+        &self,
+        endpoint: &str,
+        policy: &RetryPolicy,
+        attempts: u32,
+        payload: &P,
+        e: &ApiError,
+    ) -> bool {
+        if !policy.is_retryable(e) || self.sync.stage() == GameStage::Over {
+            return false;
+        }
+        if policy.is_exhausted(attempts) {
+            if let Some(letters) = &self.sync.dead_letters {
+                letters.write(endpoint, payload, attempts, e);
+            }
+            self.sync
+                .metrics_sender
+                .send(Metrics::new_retry_exhausted())
+                .await
+                .unwrap();
+            return false;
+        }
+        self.sync
+            .metrics_sender
+            .send(Metrics::new_retry())
+            .await
+            .unwrap();
+        task::sleep(policy.backoff(attempts)).await;
+        true
+    }
+}
+
+#[async_trait]
+impl Game for GameClient {
+    async fn explore(&self, area: Area) -> Result<Tile, ApiError> {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            self.sync.explore_rate_limiter().await;
+            let result: Result<Tile, ApiError> = http_post(
+                &self.explore_url,
+                self.timeout,
+                area.clone(),
+                &self.client,
+                self.sync.clone(),
+            )
+            .await;
+            match result {
+                Ok(tile) => {
+                    self.sync
+                        .metrics_sender
+                        .send(Metrics::new_explore(
+                            tile.is_single_point() && tile.amount > 0,
+                            tile.area.pos_x,
+                            tile.area.pos_y,
+                            tile.area.size_x,
+                            tile.area.size_y,
+                        ))
+                        .await
+                        .unwrap();
+                    return Ok(tile);
+                }
+                Err(e) => {
+                    if !self.sync.is_running()
+                        || !self
+                            .retry("explore", &self.explore_retry, attempts, &area, &e)
+                            .await
+                    {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+    }
+
+    async fn dig(&self, dig: Dig) -> Result<TreasureList, ApiError> {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            self.sync.digger_rate_limiter().await;
+            let result = http_post(
+                &self.dig_url,
+                self.timeout,
+                dig,
+                &self.client,
+                self.sync.clone(),
+            )
+            .await;
+            self.sync
+                .metrics_sender
+                .send(Metrics::new_dig(result.is_ok(), dig.depth))
+                .await
+                .unwrap();
+            match result {
+                Err(e) if self.retry("dig", &self.dig_retry, attempts, &dig, &e).await => {}
+                // the level is dug but what it held is lost. retry() has already
+                // kept the dead letter if the policy retries decode failures
+                Err(e @ ApiError::Decode(_)) => {
+                    if !self.dig_retry.is_retryable(&e) {
+                        if let Some(letters) = &self.sync.dead_letters {
+                            letters.write("dig", dig, attempts, &e);
+                        }
+                    }
+                    return Err(e);
+                }
+                _ => return result,
+            }
+        }
+    }
+
+    async fn issue_license(&self, coins: MoneyList) -> Result<License, ApiError> {
+        let price = coins.len() as u64;
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            self.sync.attorney_rate_limiter().await;
+            let result: Result<License, ApiError> = http_post(
+                &self.licenses_url,
+                self.license_timeout,
+                coins.clone(),
+                &self.client,
+                self.sync.clone(),
+            )
+            .await;
+            let metric = match &result {
+                Ok(license) => Metrics::new_license_paid(license.dig_allowed, price),
+                Err(ApiError::PaymentRequired) => return result,
+                Err(ApiError::TooManyLicenses) => Metrics::new_license_rejected(),
+                Err(_) => Metrics::new_license(0, price),
+            };
+            self.sync.metrics_sender.send(metric).await.unwrap();
+            match result {
+                Err(e)
+                    if self.sync.is_running()
+                        && self
+                            .retry("licenses", &self.license_retry, attempts, &coins, &e)
+                            .await => {}
+                _ => return result,
+            }
+        }
+    }
+
+    async fn cash(&self, treasure: String) -> Result<MoneyList, ApiError> {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            self.sync.accountant_rate_limiter().await;
+            let result: Result<MoneyList, ApiError> = http_post(
+                &self.cash_url,
+                self.cash_timeout,
+                treasure.clone(),
+                &self.client,
+                self.sync.clone(),
+            )
+            .await;
+            let metric = match &result {
+                Ok(money) => Metrics::new_cash(money.len() as u64, true),
+                Err(_) => Metrics::new_cash(0u64, false),
+            };
+            self.sync.metrics_sender.send(metric).await.unwrap();
+            match result {
+                Err(e) if self.retry("cash", &self.cash_retry, attempts, &treasure, &e).await => {}
+                _ => return result,
+            }
+        }
+    }
+
+    async fn balance(&self) -> Result<Balance, ApiError> {
+        let body = http_get(
+            &self.balance_url,
+            self.timeout,
+            &self.client,
+            self.sync.clone(),
+        )
+        .await?;
+        serde_json::from_str(&body).map_err(|e| ApiError::Decode(e.to_string()))
+    }
+
+    async fn health(&self) -> Result<(), ApiError> {
+        http_get(
+            &self.health_url,
+            self.health_timeout,
+            &self.client,
+            self.sync.clone(),
+        )
+        .await
+        .map(|_| ())
+    }
+}
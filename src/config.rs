@@ -1,3 +1,7 @@
+use crate::pricing::LicenseTiers;
+use crate::queue::{CashOrder, TieBreak};
+use crate::retry::RetryPolicy;
+use crate::search::SearchStrategyKind;
 use envconfig::Envconfig;
 use std::env;
 use std::fmt::{Display, Formatter, Result};
@@ -12,8 +16,8 @@ pub struct Config {
     #[envconfig(from = "ACCOUNTANT_NUM", default = "8")]
     pub accountant_num: u64,
 
-    #[envconfig(from = "SEARCH_BINARY_ENABLED", default = "true")]
-    pub search_binary_enabled: bool,
+    #[envconfig(from = "SEARCH_STRATEGY", default = "binary")]
+    pub search_strategy: SearchStrategyKind,
     #[envconfig(from = "SEARCH_INITIAL_ARRAY_SIZE", default = "31")]
     pub search_initial_array_size: u64,
     #[envconfig(from = "SEARCH_MIN_AMOUNT", default = "1")]
@@ -22,20 +26,31 @@ pub struct Config {
     pub search_to_flat_threshold: u64,
     #[envconfig(from = "SEARCH_FLAT_SIZE", default = "3")]
     pub search_flat_size: u64,
+    #[envconfig(from = "SEARCH_BLOCK_SIZE_X", default = "32")]
+    pub search_block_size_x: u64,
+    #[envconfig(from = "SEARCH_BLOCK_SIZE_Y", default = "16")]
+    pub search_block_size_y: u64,
+    #[envconfig(from = "SEARCH_PLANNER_MAX_SPLIT", default = "8")]
+    pub search_planner_max_split: u64,
     #[envconfig(from = "SEARCH_EXPLORERS_NUM", default = "8")]
     pub search_explorers_num: u64,
 
+    // treasures at this depth and shallower are cashed only while their learned
+    // value pays for the cash request, deeper ones are always cashed
     #[envconfig(from = "DIGGER_MIN_DEPTH", default = "3")]
     pub digger_min_depth: u64,
     #[envconfig(from = "DIGGER_MAX_DEPTH", default = "10")]
     pub digger_max_depth: u64,
-    #[envconfig(from = "DIGGER_MIN_DEPTH_PROBABILITY", default = "100")]
-    pub digger_min_depth_probability: u64,
+    #[envconfig(from = "DIGGER_POINTS_IN_FLIGHT", default = "1")]
+    pub digger_points_in_flight: u64,
 
     #[envconfig(from = "ATTORNEY_LICENSE_MIN_COST", default = "1")]
     pub attorney_license_min_cost: u64,
     #[envconfig(from = "ATTORNEY_LICENSE_MAX_COST", default = "1")]
     pub attorney_license_max_cost: u64,
+    // every tier must cost ATTORNEY_LICENSE_MIN_COST..MAX_COST coins
+    #[envconfig(from = "LICENSE_TIERS", default = "1:5")]
+    pub license_tiers: LicenseTiers,
     #[envconfig(from = "ATTORNEY_FREE_LICENSE_PROBABILITY", default = "60")]
     pub attorney_free_license_probability: u64,
     #[envconfig(from = "ATTORNEY_HTTP_TIMEOUT_MS", default = "120")]
@@ -44,16 +59,25 @@ pub struct Config {
     #[envconfig(from = "ACCOUNTANT_HTTP_TIMEOUT_MS", default = "100")]
     pub accountant_http_timeout_ms: u64,
 
+    #[envconfig(from = "BANKER_PERIOD_SEC", default = "10")]
+    pub banker_period_sec: u64,
+    #[envconfig(from = "BANKER_RESEED", default = "false")]
+    pub banker_reseed: bool,
+
     #[envconfig(from = "AREA_CHAN_CAP", default = "5")]
     pub area_chan_cap: usize,
     #[envconfig(from = "TILE_CHAN_CAP", default = "5")]
     pub tile_chan_cap: usize,
-    #[envconfig(from = "LICENSE_CHAN_CAP", default = "30")]
-    pub license_chan_cap: usize,
-    #[envconfig(from = "EMPTY_LICENSE_CHAN_CAP", default = "10")]
-    pub empty_license_chan_cap: usize,
+    #[envconfig(from = "DIG_QUEUE_CAP", default = "200")]
+    pub dig_queue_cap: usize,
+    #[envconfig(from = "DIG_QUEUE_TIE_BREAK", default = "fifo")]
+    pub dig_queue_tie_break: TieBreak,
+    #[envconfig(from = "LICENSE_ACTIVE_LIMIT", default = "10")]
+    pub license_active_limit: usize,
     #[envconfig(from = "TREASURE_CHAN_CAP", default = "100")]
     pub treasure_chan_cap: usize,
+    #[envconfig(from = "CASH_QUEUE_ORDER", default = "depth")]
+    pub cash_queue_order: CashOrder,
 
     #[envconfig(from = "STATIST_DISPLAY_TICK", default = "10")]
     pub statist_display_tick: u64,
@@ -77,17 +101,76 @@ pub struct Config {
     pub digger_phase2_rps: u32,
     #[envconfig(from = "ATTORNEY_PHASE2_RPS", default = "1")]
     pub attorney_phase2_rps: u32,
+    #[envconfig(from = "ACCOUNTANT_DRAIN_RPS", default = "400")]
+    pub accountant_drain_rps: u32,
 
     #[envconfig(from = "ENABLE_PHASED", default = "false")]
     pub enable_phased: bool,
     #[envconfig(from = "PHASE2_START", default = "450")]
     pub phase2_start: u64,
 
+    #[envconfig(from = "GAME_DURATION_SEC", default = "0")]
+    pub game_duration_sec: u64,
+    #[envconfig(from = "GAME_DRAIN_SEC", default = "60")]
+    pub game_drain_sec: u64,
+
     #[envconfig(from = "HTTP_TIMEOUT_MS", default = "500")]
     pub http_timeout_ms: u64,
 
+    #[envconfig(from = "HEALTH_CHECK_TIMEOUT_MS", default = "100")]
+    pub health_check_timeout_ms: u64,
+    #[envconfig(from = "HEALTH_CHECK_BACKOFF_MS", default = "10")]
+    pub health_check_backoff_ms: u64,
+    #[envconfig(from = "HEALTH_CHECK_MAX_BACKOFF_MS", default = "500")]
+    pub health_check_max_backoff_ms: u64,
+    // startup fails if the server isn't up, or with BANKER_RESEED the balance
+    // can't be read, after this long
+    #[envconfig(from = "HEALTH_CHECK_DEADLINE_MS", default = "30000")]
+    pub health_check_deadline_ms: u64,
+
+    #[envconfig(
+        from = "RETRY_EXPLORE",
+        default = "attempts=20,backoff=1..100,on=transport|timeout|decode|rate_limited|server_error"
+    )]
+    pub retry_explore: RetryPolicy,
+    #[envconfig(
+        from = "RETRY_DIG",
+        default = "attempts=10,backoff=1..100,on=transport|timeout|rate_limited|server_error"
+    )]
+    pub retry_dig: RetryPolicy,
+    #[envconfig(
+        from = "RETRY_LICENSES",
+        default = "attempts=20,backoff=1..100,on=transport|timeout|decode|rate_limited|server_error"
+    )]
+    pub retry_licenses: RetryPolicy,
+    #[envconfig(
+        from = "RETRY_CASH",
+        default = "attempts=10,backoff=1..100,on=transport|timeout|rate_limited|server_error"
+    )]
+    pub retry_cash: RetryPolicy,
+
+    #[envconfig(from = "BREAKER_ERROR_RATIO", default = "0.5")]
+    pub breaker_error_ratio: f64,
+    #[envconfig(from = "BREAKER_WINDOW_MS", default = "1000")]
+    pub breaker_window_ms: u64,
+    #[envconfig(from = "BREAKER_MIN_REQUESTS", default = "20")]
+    pub breaker_min_requests: u64,
+    #[envconfig(from = "BREAKER_OPEN_MS", default = "500")]
+    pub breaker_open_ms: u64,
+    #[envconfig(from = "BREAKER_PROBES", default = "3")]
+    pub breaker_probes: u64,
+
     #[envconfig(from = "WORLD_SIZE", default = "3500")]
     pub world_size: u64,
+
+    #[envconfig(from = "MOCK_SERVER_ENABLED", default = "false")]
+    pub mock_server_enabled: bool,
+    #[envconfig(from = "MOCK_SEED", default = "1")]
+    pub mock_seed: u64,
+    #[envconfig(from = "MOCK_TREASURE_DENSITY", default = "0.04")]
+    pub mock_treasure_density: f64,
+    #[envconfig(from = "MOCK_ACTIVE_LICENSE_LIMIT", default = "10")]
+    pub mock_active_license_limit: usize,
 }
 
 impl Config {
@@ -102,6 +185,34 @@ impl Config {
         url.set_host(Some(host.as_str())).unwrap();
         url
     }
+    // settings that parse but can't be played with
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        self.license_tiers.validate(
+            self.attorney_license_min_cost as usize,
+            self.attorney_license_max_cost as usize,
+        )
+    }
+    pub fn get_http_record_file(&self) -> Option<String> {
+        env::var("HTTP_RECORD_FILE").ok().filter(|f| !f.is_empty())
+    }
+    pub fn get_http_replay_file(&self) -> Option<String> {
+        env::var("HTTP_REPLAY_FILE").ok().filter(|f| !f.is_empty())
+    }
+    pub fn get_retry_dead_letter_file(&self) -> Option<String> {
+        env::var("RETRY_DEAD_LETTER_FILE").ok().filter(|f| !f.is_empty())
+    }
+    pub fn get_wal_file(&self) -> Option<String> {
+        env::var("WAL_FILE").ok().filter(|f| !f.is_empty())
+    }
+    pub fn get_checkpoint_file(&self) -> Option<String> {
+        env::var("CHECKPOINT_FILE").ok().filter(|f| !f.is_empty())
+    }
+    pub fn get_mock_faults_file(&self) -> Option<String> {
+        env::var("MOCK_FAULTS_FILE").ok().filter(|f| !f.is_empty())
+    }
+    pub fn get_mock_server_addr(&self) -> String {
+        format!("0.0.0.0:{}", self.get_url().port().unwrap())
+    }
     pub fn get_explore_url(&self) -> Url {
         self.get_url().join("explore").unwrap()
     }
@@ -114,21 +225,31 @@ impl Config {
     pub fn get_cash_url(&self) -> Url {
         self.get_url().join("cash").unwrap()
     }
+    pub fn get_balance_url(&self) -> Url {
+        self.get_url().join("balance").unwrap()
+    }
+    pub fn get_health_check_url(&self) -> Url {
+        self.get_url().join("health-check").unwrap()
+    }
 }
 
 impl Display for Config {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         write!(
             f,
-            "[e={},d={},at={},ac={}][depth={}-{}({})][bin={},array_size={},min={},fthres={},fsize={}][lic={}-{}({}%][ht={},aht={}][ph={}({})]",
+            "[e={},d={}x{},at={},ac={}][depth={}-{},queue={},cash={}][search={},block={}x{},array_size={},min={},fthres={},fsize={}][lic={}-{}({}%,tiers={}][ht={},aht={}][ph={}({})][game={}-{}]",
             self.search_explorers_num,
             self.diggers_num,
+            self.digger_points_in_flight,
             self.attorneys_num,
             self.accountant_num,
             self.digger_min_depth,
             self.digger_max_depth,
-            self.digger_min_depth_probability,
-            self.search_binary_enabled,
+            self.dig_queue_tie_break,
+            self.cash_queue_order,
+            self.search_strategy,
+            self.search_block_size_x,
+            self.search_block_size_y,
             self.search_initial_array_size,
             self.search_min_amount,
             self.search_to_flat_threshold,
@@ -136,10 +257,94 @@ impl Display for Config {
             self.attorney_license_min_cost,
             self.attorney_license_max_cost,
             self.attorney_free_license_probability,
+            self.license_tiers,
             self.http_timeout_ms,
             self.attorney_http_timeout_ms,
             self.enable_phased,
-            self.phase2_start
+            self.phase2_start,
+            self.game_duration_sec,
+            self.game_drain_sec
         )
     }
 }
+
+// every setting at its default, whatever the environment of the test run says
+#[cfg(test)]
+impl Config {
+    pub fn for_tests() -> Config {
+        Config {
+            attorneys_num: 8,
+            diggers_num: 8,
+            accountant_num: 8,
+            search_strategy: "binary".parse().unwrap(),
+            search_initial_array_size: 31,
+            search_min_amount: 1,
+            search_to_flat_threshold: 31,
+            search_flat_size: 3,
+            search_block_size_x: 32,
+            search_block_size_y: 16,
+            search_planner_max_split: 8,
+            search_explorers_num: 8,
+            digger_min_depth: 3,
+            digger_max_depth: 10,
+            digger_points_in_flight: 1,
+            attorney_license_min_cost: 1,
+            attorney_license_max_cost: 1,
+            license_tiers: "1:5".parse().unwrap(),
+            attorney_free_license_probability: 60,
+            attorney_http_timeout_ms: 120,
+            accountant_http_timeout_ms: 100,
+            banker_period_sec: 10,
+            banker_reseed: false,
+            area_chan_cap: 5,
+            tile_chan_cap: 5,
+            dig_queue_cap: 200,
+            dig_queue_tie_break: "fifo".parse().unwrap(),
+            license_active_limit: 10,
+            treasure_chan_cap: 100,
+            cash_queue_order: "depth".parse().unwrap(),
+            statist_display_tick: 10,
+            max_rps: 1000,
+            explore_phase1_rps: 650,
+            accountant_phase1_rps: 100,
+            digger_phase1_rps: 400,
+            attorney_phase1_rps: 400,
+            explore_phase2_rps: 1,
+            accountant_phase2_rps: 400,
+            digger_phase2_rps: 1,
+            attorney_phase2_rps: 1,
+            accountant_drain_rps: 400,
+            enable_phased: false,
+            phase2_start: 450,
+            game_duration_sec: 0,
+            game_drain_sec: 60,
+            http_timeout_ms: 500,
+            health_check_timeout_ms: 100,
+            health_check_backoff_ms: 10,
+            health_check_max_backoff_ms: 500,
+            health_check_deadline_ms: 30000,
+            retry_explore: "attempts=20,backoff=1..100,on=transport|timeout|decode|rate_limited|server_error"
+                .parse()
+                .unwrap(),
+            retry_dig: "attempts=10,backoff=1..100,on=transport|timeout|rate_limited|server_error"
+                .parse()
+                .unwrap(),
+            retry_licenses: "attempts=20,backoff=1..100,on=transport|timeout|decode|rate_limited|server_error"
+                .parse()
+                .unwrap(),
+            retry_cash: "attempts=10,backoff=1..100,on=transport|timeout|rate_limited|server_error"
+                .parse()
+                .unwrap(),
+            breaker_error_ratio: 0.5,
+            breaker_window_ms: 1000,
+            breaker_min_requests: 20,
+            breaker_open_ms: 500,
+            breaker_probes: 3,
+            world_size: 3500,
+            mock_server_enabled: false,
+            mock_seed: 1,
+            mock_treasure_density: 0.04,
+            mock_active_license_limit: 10,
+        }
+    }
+}
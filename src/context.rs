@@ -1,5 +1,16 @@
+use crate::checkpoint::{Checkpoint, CheckpointEvent};
 use crate::config::Config;
-use crate::model::{License, MoneyList, Tile};
+use crate::http::breaker::{BreakerConfig, Breakers};
+use crate::http::record::{Recorder, Replayer};
+use crate::map::WorldMap;
+use crate::license::LicenseManager;
+use crate::model::Tile;
+use crate::pricing::LicensePricing;
+use crate::queue::{DigQueue, TreasureQueue};
+use crate::retry::DeadLetters;
+use crate::value::ValueEstimator;
+use crate::wal::{Wal, WalEvent};
+use crate::wallet::Wallet;
 use async_std::channel::{bounded, unbounded};
 use async_std::channel::{Receiver, Sender};
 use core::num::NonZeroU32;
@@ -8,7 +19,9 @@ use governor::state::{InMemoryState, NotKeyed};
 use governor::{Quota, RateLimiter};
 use num_integer::Integer;
 use std::convert::TryFrom;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 
 #[derive(Clone)]
 pub struct SyncContext {
@@ -16,17 +29,23 @@ pub struct SyncContext {
     pub area_receiver: Receiver<Tile>,
     pub tile_sender: Sender<Tile>,
     pub tile_receiver: Receiver<Tile>,
-    pub license_sender: Sender<License>,
-    pub license_receiver: Receiver<License>,
-    pub empty_license_sender: Sender<License>,
-    pub empty_license_receiver: Receiver<License>,
-    pub treasure_sender: Sender<String>,
-    pub treasure_receiver: Receiver<String>,
-    pub cash_sender: Sender<MoneyList>,
-    pub cash_receiver: Receiver<MoneyList>,
+    pub dig_queue: Arc<DigQueue>,
+    pub licenses: Arc<LicenseManager>,
+    pub treasures: Arc<TreasureQueue>,
+    pub wallet: Arc<Wallet>,
     pub metrics_sender: Sender<Metrics>,
     pub metrics_receiver: Receiver<Metrics>,
     pub metrics: Arc<Mutex<Metrics>>,
+    pub map: Arc<RwLock<WorldMap>>,
+    pub values: Arc<RwLock<ValueEstimator>>,
+    pub pricing: Arc<RwLock<LicensePricing>>,
+    pub recorder: Option<Arc<Recorder>>,
+    pub replayer: Option<Arc<Replayer>>,
+    pub dead_letters: Option<Arc<DeadLetters>>,
+    // opened by main once it has recovered the log
+    pub wal: Option<Arc<Wal>>,
+    pub checkpoint: Option<Arc<Checkpoint>>,
+    pub breakers: Arc<Breakers>,
     http_rate_limiter: Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>>,
     explore_phase1_rate_limiter: Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>>,
     explore_phase2_rate_limiter: Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>>,
@@ -36,17 +55,33 @@ pub struct SyncContext {
     attorney_phase2_rate_limiter: Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>>,
     accountant_phase1_rate_limiter: Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>>,
     accountant_phase2_rate_limiter: Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>>,
+    accountant_drain_rate_limiter: Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>>,
     phase2: Arc<RwLock<bool>>,
+    stage: Arc<RwLock<GameStage>>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GameStage {
+    Running,
+    // no more explores and licenses, diggers finish their points, accountants cash
+    Draining,
+    Over,
+}
+
+pub struct InFlightGuard(Arc<AtomicUsize>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
 }
 
 impl SyncContext {
     pub fn new(c: Config) -> SyncContext {
         let (area_sender, area_receiver) = bounded(c.area_chan_cap);
         let (tile_sender, tile_receiver) = bounded(c.tile_chan_cap);
-        let (license_sender, license_receiver) = bounded(c.license_chan_cap);
-        let (empty_license_sender, empty_license_receiver) = bounded(c.empty_license_chan_cap);
-        let (treasure_sender, treasure_receiver) = bounded(c.treasure_chan_cap);
-        let (cash_sender, cash_receiver) = unbounded();
+        let values = Arc::new(RwLock::new(ValueEstimator::new(c.digger_max_depth)));
         let (metrics_sender, metrics_receiver) = unbounded();
 
         SyncContext {
@@ -54,17 +89,34 @@ impl SyncContext {
             area_receiver,
             tile_sender,
             tile_receiver,
-            license_sender,
-            license_receiver,
-            empty_license_sender,
-            empty_license_receiver,
-            treasure_sender,
-            treasure_receiver,
-            cash_sender,
-            cash_receiver,
+            dig_queue: Arc::new(DigQueue::new(
+                c.dig_queue_cap,
+                c.digger_max_depth,
+                c.dig_queue_tie_break,
+            )),
+            licenses: Arc::new(LicenseManager::new(c.license_active_limit)),
+            treasures: Arc::new(TreasureQueue::new(
+                c.treasure_chan_cap,
+                c.cash_queue_order,
+                values.clone(),
+            )),
+            wallet: Arc::new(Wallet::new()),
             metrics_sender,
             metrics_receiver,
             metrics: Arc::new(Mutex::new(Metrics::new())),
+            map: Arc::new(RwLock::new(WorldMap::new(c.world_size))),
+            values,
+            pricing: Arc::new(RwLock::new(LicensePricing::new(
+                c.license_tiers,
+                c.attorney_license_min_cost as usize,
+                c.attorney_license_max_cost as usize,
+            ))),
+            recorder: None,
+            replayer: None,
+            dead_letters: None,
+            wal: None,
+            checkpoint: None,
+            breakers: Arc::new(Breakers::new(BreakerConfig::new(&c))),
             http_rate_limiter: Arc::new(RateLimiter::direct(Quota::per_second(
                 NonZeroU32::try_from(c.max_rps).unwrap(),
             ))),
@@ -92,12 +144,12 @@ impl SyncContext {
             accountant_phase2_rate_limiter: Arc::new(RateLimiter::direct(Quota::per_second(
                 NonZeroU32::try_from(c.accountant_phase2_rps).unwrap(),
             ))),
+            accountant_drain_rate_limiter: Arc::new(RateLimiter::direct(Quota::per_second(
+                NonZeroU32::try_from(c.accountant_drain_rps).unwrap(),
+            ))),
             phase2: Arc::new(RwLock::new(false)),
-        }
-    }
-    pub async fn init(&self) {
-        for _ in 0..self.empty_license_sender.capacity().unwrap() {
-            &self.empty_license_sender.send(License::new()).await;
+            stage: Arc::new(RwLock::new(GameStage::Running)),
+            in_flight: Arc::new(AtomicUsize::new(0)),
         }
     }
     pub async fn http_rate_limiter(&self) {
@@ -126,7 +178,9 @@ impl SyncContext {
         }
     }
     pub async fn accountant_rate_limiter(&self) {
-        if *self.phase2.read().unwrap(){
+        if self.stage() == GameStage::Draining {
+            self.accountant_drain_rate_limiter.until_ready().await
+        } else if *self.phase2.read().unwrap(){
             self.accountant_phase2_rate_limiter.until_ready().await
         } else {
             self.accountant_phase1_rate_limiter.until_ready().await
@@ -150,6 +204,62 @@ impl SyncContext {
     pub fn is_phase2(&self) -> bool{
         *self.phase2.read().unwrap()
     }
+    pub fn start_drain(&mut self) {
+        *self.stage.write().unwrap() = GameStage::Draining
+    }
+    pub fn finish_game(&mut self) {
+        *self.stage.write().unwrap() = GameStage::Over
+    }
+    pub fn stage(&self) -> GameStage {
+        *self.stage.read().unwrap()
+    }
+    pub fn is_running(&self) -> bool {
+        self.stage() == GameStage::Running
+    }
+    pub fn log(&self, event: WalEvent) {
+        if let Some(wal) = &self.wal {
+            wal.append(&event);
+        }
+    }
+    pub fn save_progress(&self, event: CheckpointEvent) {
+        if let Some(checkpoint) = &self.checkpoint {
+            checkpoint.append(&event);
+        }
+    }
+    pub fn begin_request(&self) -> InFlightGuard {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard(self.in_flight.clone())
+    }
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+}
+
+// stepwise explore cost by area
+pub fn explore_price(square: u64) -> f32 {
+    if square == 0 {
+        0.0
+    } else if square < 4 {
+        0.5
+    } else if square < 8 {
+        1.0
+    } else if square < 16 {
+        1.5
+    } else if square < 32 {
+        2.0
+    } else if square < 64 {
+        2.5
+    } else if square < 128 {
+        3.0
+    } else if square < 256 {
+        3.5
+    } else if square < 512{
+        4.0
+    } else if square < 1024{
+        4.5
+    } else {
+        100.0
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -162,6 +272,7 @@ pub struct Metrics {
     pub cash_value: u64,
     pub cash_price: f32,
     pub license_count: u64,
+    pub license_rejected: u64,
     pub license_value: u64,
     pub license_price: u64,
     pub explore_count: u64,
@@ -169,6 +280,13 @@ pub struct Metrics {
     pub explore_price: f32,
     pub explore_odd_x: u64,
     pub explore_odd_y: u64,
+    pub explore_plan_expected: f32,
+    pub explore_plan_actual: f32,
+    pub explore_plan_skipped: u64,
+    pub explore_known: u64,
+    pub treasure_dropped: u64,
+    pub retry_count: u64,
+    pub retry_exhausted: u64,
     pub http200: u64,
     pub http404: u64,
     pub http409: u64,
@@ -176,6 +294,13 @@ pub struct Metrics {
     pub http429: u64,
     pub http50x: u64,
     pub http_other: u64,
+    pub health_check_count: u64,
+    pub health_check_wait_ms: u64,
+    pub coins_reseeded: u64,
+    pub coins_spent: u64,
+    pub balance_checks: u64,
+    pub wallet_server: i64,
+    pub wallet_drift: i64,
 }
 
 impl Metrics {
@@ -189,6 +314,7 @@ impl Metrics {
             cash_value: 0,
             cash_price: 0.0,
             license_count: 0,
+            license_rejected: 0,
             license_value: 0,
             license_price: 0,
             explore_count: 0,
@@ -196,6 +322,13 @@ impl Metrics {
             explore_price: 0.0,
             explore_odd_x: 0,
             explore_odd_y: 0,
+            explore_plan_expected: 0.0,
+            explore_plan_actual: 0.0,
+            explore_plan_skipped: 0,
+            explore_known: 0,
+            treasure_dropped: 0,
+            retry_count: 0,
+            retry_exhausted: 0,
             http200: 0,
             http404: 0,
             http409: 0,
@@ -203,6 +336,13 @@ impl Metrics {
             http429: 0,
             http50x: 0,
             http_other: 0,
+            health_check_count: 0,
+            health_check_wait_ms: 0,
+            coins_reseeded: 0,
+            coins_spent: 0,
+            balance_checks: 0,
+            wallet_server: 0,
+            wallet_drift: 0,
         }
     }
     pub fn new200() -> Metrics {
@@ -257,6 +397,31 @@ impl Metrics {
         m.license_price += price;
         m
     }
+    // a license request refused over the active license limit (409)
+    pub fn new_license_rejected() -> Metrics {
+        let mut m = Metrics::new();
+        m.license_count += 1;
+        m.license_rejected += 1;
+        m
+    }
+    pub fn new_license_paid(value: u64, price: u64) -> Metrics {
+        let mut m = Metrics::new_license(value, price);
+        m.coins_spent += price;
+        m
+    }
+    pub fn new_coins_reseeded(coins: u64) -> Metrics {
+        let mut m = Metrics::new();
+        m.coins_reseeded += coins;
+        m
+    }
+    // server balance and drift are gauges, the banker sends them as deltas
+    pub fn new_balance(server_delta: i64, drift_delta: i64) -> Metrics {
+        let mut m = Metrics::new();
+        m.balance_checks += 1;
+        m.wallet_server += server_delta;
+        m.wallet_drift += drift_delta;
+        m
+    }
     pub fn new_dig(success: bool, _depth: u64) -> Metrics {
         let mut m = Metrics::new();
         m.dig_count += 1;
@@ -279,30 +444,36 @@ impl Metrics {
             m.explore_odd_y += 1;
         }
 
-        let square = size_x * size_y;
-        m.explore_price = if square == 0 {
-            0.0
-        } else if square < 4 {
-            0.5
-        } else if square < 8 {
-            1.0
-        } else if square < 16 {
-            1.5
-        } else if square < 32 {
-            2.0
-        } else if square < 64 {
-            2.5
-        } else if square < 128 {
-            3.0
-        } else if square < 256 {
-            3.5
-        } else if square < 512{
-            4.0
-        } else if square < 1024{
-            4.5
-        } else {
-            100.0
-        };
+        m.explore_price = explore_price(size_x * size_y);
+        m
+    }
+    pub fn new_retry() -> Metrics {
+        let mut m = Metrics::new();
+        m.retry_count += 1;
+        m
+    }
+    pub fn new_retry_exhausted() -> Metrics {
+        let mut m = Metrics::new();
+        m.retry_exhausted += 1;
+        m
+    }
+    pub fn new_treasure_dropped() -> Metrics {
+        let mut m = Metrics::new();
+        m.treasure_dropped += 1;
+        m
+    }
+    pub fn new_known_explore() -> Metrics {
+        let mut m = Metrics::new();
+        m.explore_known += 1;
+        m
+    }
+    pub fn new_explore_plan(expected: f32, actual: f32, skipped: bool) -> Metrics {
+        let mut m = Metrics::new();
+        m.explore_plan_expected += expected;
+        m.explore_plan_actual += actual;
+        if skipped {
+            m.explore_plan_skipped += 1;
+        }
         m
     }
     pub fn new_calculated_explore(success: bool, x: u64, y: u64) -> Metrics {
@@ -318,6 +489,12 @@ impl Metrics {
         }
         m
     }
+    pub fn new_health_check(wait: Duration, attempts: u64) -> Metrics {
+        let mut m = Metrics::new();
+        m.health_check_count += attempts;
+        m.health_check_wait_ms += wait.as_millis() as u64;
+        m
+    }
     pub fn sum_http(&self) -> u64 {
         self.http200
             + self.http404
@@ -366,12 +543,20 @@ impl Metrics {
         self.dig_price += other.dig_price;
         self.explore_odd_x += other.explore_odd_x;
         self.explore_odd_y += other.explore_odd_y;
+        self.explore_plan_expected += other.explore_plan_expected;
+        self.explore_plan_actual += other.explore_plan_actual;
+        self.explore_plan_skipped += other.explore_plan_skipped;
+        self.explore_known += other.explore_known;
+        self.treasure_dropped += other.treasure_dropped;
+        self.retry_count += other.retry_count;
+        self.retry_exhausted += other.retry_exhausted;
         self.explore_price += other.explore_price;
         self.cash_count += other.cash_count;
         self.cash_success += other.cash_success;
         self.cash_value += other.cash_value;
         self.cash_price += other.cash_price;
         self.license_count += other.license_count;
+        self.license_rejected += other.license_rejected;
         self.license_value += other.license_value;
         self.license_price += other.license_price;
         self.explore_count += other.explore_count;
@@ -383,5 +568,12 @@ impl Metrics {
         self.http429 += other.http429;
         self.http50x += other.http50x;
         self.http_other += other.http_other;
+        self.health_check_count += other.health_check_count;
+        self.health_check_wait_ms += other.health_check_wait_ms;
+        self.coins_reseeded += other.coins_reseeded;
+        self.coins_spent += other.coins_spent;
+        self.balance_checks += other.balance_checks;
+        self.wallet_server += other.wallet_server;
+        self.wallet_drift += other.wallet_drift;
     }
 }
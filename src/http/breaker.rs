@@ -0,0 +1,309 @@
+use crate::config::Config;
+use async_std::task;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum State {
+    // requests pass, failures are counted within the window
+    Closed {
+        since: Instant,
+        requests: u64,
+        failures: u64,
+    },
+    // requests wait until the server had time to recover
+    Open { until: Instant },
+    // a few probe requests pass, one failure opens the breaker again
+    HalfOpen { probes: u64, successes: u64 },
+}
+
+impl Display for State {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            State::Closed { .. } => write!(f, "closed"),
+            State::Open { .. } => write!(f, "open"),
+            State::HalfOpen { .. } => write!(f, "half"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct BreakerConfig {
+    pub error_ratio: f64,
+    pub window: Duration,
+    pub min_requests: u64,
+    pub open: Duration,
+    pub probes: u64,
+}
+
+impl BreakerConfig {
+    pub fn new(c: &Config) -> BreakerConfig {
+        BreakerConfig {
+            error_ratio: c.breaker_error_ratio,
+            window: Duration::from_millis(c.breaker_window_ms),
+            min_requests: c.breaker_min_requests,
+            open: Duration::from_millis(c.breaker_open_ms),
+            probes: c.breaker_probes.max(1),
+        }
+    }
+}
+
+pub struct Breaker {
+    config: BreakerConfig,
+    state: State,
+    opened: u64,
+}
+
+impl Breaker {
+    pub fn new(config: BreakerConfig, now: Instant) -> Breaker {
+        Breaker {
+            config,
+            state: State::Closed {
+                since: now,
+                requests: 0,
+                failures: 0,
+            },
+            opened: 0,
+        }
+    }
+
+    // None lets the request through, otherwise it should wait that long
+    pub fn try_pass(&mut self, now: Instant) -> Option<Duration> {
+        if let State::Open { until } = self.state {
+            if now < until {
+                return Some(until - now);
+            }
+            self.state = State::HalfOpen {
+                probes: 0,
+                successes: 0,
+            };
+        }
+        match &mut self.state {
+            State::HalfOpen { probes, .. } if *probes >= self.config.probes => {
+                // probes are in flight, look again when one may have returned
+                Some(self.config.open / 10)
+            }
+            State::HalfOpen { probes, .. } => {
+                *probes += 1;
+                None
+            }
+            _ => None,
+        }
+    }
+
+    pub fn record(&mut self, failed: bool, now: Instant) {
+        let window = self.config.window;
+        match &mut self.state {
+            State::Closed {
+                since,
+                requests,
+                failures,
+            } => {
+                if now.duration_since(*since) > window {
+                    *since = now;
+                    *requests = 0;
+                    *failures = 0;
+                }
+                *requests += 1;
+                if failed {
+                    *failures += 1;
+                }
+                if *requests >= self.config.min_requests
+                    && *failures as f64 >= *requests as f64 * self.config.error_ratio
+                {
+                    self.open(now);
+                }
+            }
+            State::HalfOpen { successes, .. } => {
+                if failed {
+                    self.open(now);
+                } else {
+                    *successes += 1;
+                    if *successes >= self.config.probes {
+                        self.state = State::Closed {
+                            since: now,
+                            requests: 0,
+                            failures: 0,
+                        };
+                    }
+                }
+            }
+            // answers of requests sent before the breaker opened
+            State::Open { .. } => {}
+        }
+    }
+
+    fn open(&mut self, now: Instant) {
+        self.opened += 1;
+        self.state = State::Open {
+            until: now + self.config.open,
+        };
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+}
+
+// a circuit breaker per endpoint path
+pub struct Breakers {
+    config: BreakerConfig,
+    enabled: bool,
+    breakers: Mutex<BTreeMap<String, Breaker>>,
+}
+
+impl Breakers {
+    pub fn new(config: BreakerConfig) -> Breakers {
+        Breakers {
+            config,
+            enabled: config.error_ratio > 0.0,
+            breakers: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    // waits while the endpoint breaker is open
+    pub async fn pass(&self, endpoint: &str) {
+        if !self.enabled {
+            return;
+        }
+        loop {
+            let wait = self
+                .breakers
+                .lock()
+                .unwrap()
+                .entry(endpoint.to_string())
+                .or_insert_with(|| Breaker::new(self.config, Instant::now()))
+                .try_pass(Instant::now());
+            match wait {
+                Some(wait) => task::sleep(wait).await,
+                None => return,
+            }
+        }
+    }
+
+    pub fn record(&self, endpoint: &str, failed: bool) {
+        if !self.enabled {
+            return;
+        }
+        if let Some(breaker) = self.breakers.lock().unwrap().get_mut(endpoint) {
+            breaker.record(failed, Instant::now());
+        }
+    }
+
+    // "endpoint:state(times opened)" for every endpoint, e.g. "/dig:open(2)"
+    pub fn summary(&self) -> String {
+        self.breakers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(endpoint, b)| format!("{}:{}({})", endpoint, b.state(), b.opened))
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::context::SyncContext;
+    use crate::http::breaker::{Breaker, BreakerConfig, Breakers, State};
+    use crate::http::{http_post, ApiError};
+    use crate::mock::faults::{EndpointFaults, FaultInjector};
+    use crate::mock::world::World;
+    use crate::mock::MockServer;
+    use crate::model::{Area, Tile};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use url::Url;
+
+    fn config() -> BreakerConfig {
+        BreakerConfig {
+            error_ratio: 0.5,
+            window: Duration::from_millis(1000),
+            min_requests: 4,
+            open: Duration::from_millis(100),
+            probes: 2,
+        }
+    }
+
+    #[test]
+    fn test_breaker() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut breaker = Breaker::new(config(), start);
+        for failed in [true, false, true] {
+            assert_eq!(breaker.try_pass(at(0)), None);
+            breaker.record(failed, at(0));
+        }
+        assert_eq!(breaker.state().to_string(), "closed");
+        breaker.record(true, at(10));
+        assert_eq!(breaker.state(), State::Open { until: at(110) });
+        assert_eq!(breaker.try_pass(at(60)), Some(Duration::from_millis(50)));
+
+        // two probes pass, a third waits, a failed probe opens it again
+        assert_eq!(breaker.try_pass(at(110)), None);
+        assert_eq!(breaker.try_pass(at(110)), None);
+        assert!(breaker.try_pass(at(110)).is_some());
+        breaker.record(true, at(120));
+        assert_eq!(breaker.state(), State::Open { until: at(220) });
+
+        assert_eq!(breaker.try_pass(at(220)), None);
+        assert_eq!(breaker.try_pass(at(220)), None);
+        breaker.record(false, at(230));
+        breaker.record(false, at(230));
+        assert_eq!(breaker.state().to_string(), "closed");
+
+        // failures of an old window are forgotten
+        for _ in 0..3 {
+            breaker.record(true, at(300));
+        }
+        breaker.record(false, at(1400));
+        assert_eq!(breaker.state().to_string(), "closed");
+    }
+
+    #[async_std::test]
+    async fn test_breaker_against_failing_server() {
+        let mut scenario = HashMap::new();
+        scenario.insert(
+            "explore".to_string(),
+            EndpointFaults {
+                error_5xx_rate: 1.0,
+                ..EndpointFaults::default()
+            },
+        );
+        let listener = MockServer::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}/explore", listener.local_addr().unwrap())).unwrap();
+        let mock = MockServer::from_world(World::new(8, 10, 20, 64, 5))
+            .with_faults(FaultInjector::new(scenario, 5));
+        async_std::task::spawn(async move { mock.listen(listener).await });
+
+        let mut sync = SyncContext::new(Config::for_tests());
+        sync.breakers = Arc::new(Breakers::new(config()));
+        let client = surf::Client::new();
+        let explore = || async {
+            let result: Result<Tile, ApiError> = http_post(
+                &url,
+                Duration::from_millis(1000),
+                Area::new(0, 0, 1, 1),
+                &client,
+                sync.clone(),
+            )
+            .await;
+            assert!(matches!(result, Err(ApiError::ServerError(503, _))));
+        };
+
+        for _ in 0..4 {
+            explore().await;
+        }
+        assert_eq!(sync.breakers.summary(), "/explore:open(1)");
+
+        // the next request waits for the breaker to half-open, fails as a probe
+        let started = Instant::now();
+        explore().await;
+        assert!(started.elapsed() >= Duration::from_millis(90));
+        assert_eq!(sync.breakers.summary(), "/explore:open(2)");
+    }
+}
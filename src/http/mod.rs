@@ -0,0 +1,260 @@
+pub mod breaker;
+pub mod record;
+
+use crate::context::{Metrics, SyncContext};
+use async_std::future;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::time::{Duration, Instant};
+use surf::http::convert::{Deserialize, DeserializeOwned, Serialize};
+use surf::StatusCode;
+use url::Url;
+
+// error body of the api
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HttpError {
+    pub detail: String,
+    pub status: u16,
+    pub title: String,
+}
+
+impl Display for HttpError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "status: {}, title: {}, detail: {}",
+            self.status, self.title, self.detail
+        )
+    }
+}
+
+impl HttpError {
+    pub fn new(status: u16, title: String, detail: String) -> HttpError {
+        HttpError {
+            detail,
+            status,
+            title,
+        }
+    }
+}
+
+// what went wrong with a request, as far as the workers care
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiError {
+    // the server didn't answer
+    Transport(String),
+    Timeout,
+    // the answer doesn't match the endpoint response
+    Decode(String),
+    // 404 - nothing at the dug point or an unknown treasure
+    NoTreasure,
+    // 403 - the license is used up or unknown
+    LicenseExhausted,
+    // 402
+    PaymentRequired,
+    // 409 - the active license limit is reached
+    TooManyLicenses,
+    // 400 and 422
+    BadRequest(String),
+    // 429
+    RateLimited,
+    // 5xx and any status the api doesn't define
+    ServerError(u16, String),
+}
+
+pub const ERROR_KINDS: [&str; 10] = [
+    "transport",
+    "timeout",
+    "decode",
+    "no_treasure",
+    "license_exhausted",
+    "payment_required",
+    "too_many_licenses",
+    "bad_request",
+    "rate_limited",
+    "server_error",
+];
+
+impl ApiError {
+    // position of the error in ERROR_KINDS
+    pub fn kind(&self) -> usize {
+        match self {
+            ApiError::Transport(_) => 0,
+            ApiError::Timeout => 1,
+            ApiError::Decode(_) => 2,
+            ApiError::NoTreasure => 3,
+            ApiError::LicenseExhausted => 4,
+            ApiError::PaymentRequired => 5,
+            ApiError::TooManyLicenses => 6,
+            ApiError::BadRequest(_) => 7,
+            ApiError::RateLimited => 8,
+            ApiError::ServerError(..) => 9,
+        }
+    }
+
+    pub fn from_response(status: StatusCode, body: &[u8]) -> ApiError {
+        let detail = match serde_json::from_slice::<HttpError>(body) {
+            Ok(e) => e.detail,
+            Err(_) => String::from_utf8_lossy(body).to_string(),
+        };
+        match status {
+            StatusCode::NotFound => ApiError::NoTreasure,
+            StatusCode::Forbidden => ApiError::LicenseExhausted,
+            StatusCode::PaymentRequired => ApiError::PaymentRequired,
+            StatusCode::Conflict => ApiError::TooManyLicenses,
+            StatusCode::BadRequest | StatusCode::UnprocessableEntity => {
+                ApiError::BadRequest(detail)
+            }
+            StatusCode::TooManyRequests => ApiError::RateLimited,
+            _ => ApiError::ServerError(u16::from(status), detail),
+        }
+    }
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiError::Transport(e) => write!(f, "transport error: {}", e),
+            ApiError::Timeout => write!(f, "timeout"),
+            ApiError::Decode(e) => write!(f, "decode error: {}", e),
+            ApiError::NoTreasure => write!(f, "404 no treasure"),
+            ApiError::LicenseExhausted => write!(f, "403 license exhausted"),
+            ApiError::PaymentRequired => write!(f, "402 payment required"),
+            ApiError::TooManyLicenses => write!(f, "409 too many licenses"),
+            ApiError::BadRequest(e) => write!(f, "bad request: {}", e),
+            ApiError::RateLimited => write!(f, "429 rate limited"),
+            ApiError::ServerError(status, e) => write!(f, "server error {}: {}", status, e),
+        }
+    }
+}
+
+impl Error for ApiError {}
+
+pub async fn http_get(
+    url: &Url,
+    timeout: Duration,
+    client: &surf::Client,
+    sync: SyncContext,
+) -> Result<String, ApiError> {
+    let started = Instant::now();
+    let exchange = match &sync.replayer {
+        Some(replayer) => replayer.next(url, &serde_json::Value::Null),
+        None => fetch(url, timeout, client).await,
+    };
+    if let Some(recorder) = &sync.recorder {
+        recorder.write(url, &serde_json::Value::Null, &exchange, started.elapsed());
+    }
+    let (status, body) = exchange?;
+    if status.is_success() {
+        String::from_utf8(body).map_err(|e| ApiError::Decode(e.to_string()))
+    } else {
+        Err(ApiError::from_response(status, &body))
+    }
+}
+
+pub async fn http_post<T>(
+    url: &Url,
+    timeout: Duration,
+    payload: impl Serialize,
+    client: &surf::Client,
+    sync: SyncContext,
+) -> Result<T, ApiError>
+where
+    T: Serialize + DeserializeOwned,
+{
+    sync.breakers.pass(url.path()).await;
+    sync.http_rate_limiter().await;
+    let _in_flight = sync.begin_request();
+    let payload = serde_json::to_value(&payload).unwrap();
+    let started = Instant::now();
+    let exchange = match &sync.replayer {
+        Some(replayer) => replayer.next(url, &payload),
+        None => send(url, timeout, &payload, client).await,
+    };
+    if let Some(recorder) = &sync.recorder {
+        recorder.write(url, &payload, &exchange, started.elapsed());
+    }
+    let failed = match &exchange {
+        Ok((status, _)) => status.is_server_error(),
+        Err(_) => true,
+    };
+    sync.breakers.record(url.path(), failed);
+
+    let (status, body) = match exchange {
+        Ok(response) => response,
+        Err(e) => {
+            sync.metrics_sender
+                .send(Metrics::new_other())
+                .await
+                .unwrap();
+            return Err(e);
+        }
+    };
+
+    if status == StatusCode::Ok {
+        sync.metrics_sender.send(Metrics::new200()).await.unwrap();
+    } else if status == StatusCode::NotFound {
+        sync.metrics_sender.send(Metrics::new404()).await.unwrap();
+    } else if status == StatusCode::TooManyRequests {
+        sync.metrics_sender.send(Metrics::new429()).await.unwrap();
+    } else if status == StatusCode::UnprocessableEntity {
+        sync.metrics_sender.send(Metrics::new422()).await.unwrap();
+    } else if status == StatusCode::Conflict {
+        sync.metrics_sender.send(Metrics::new409()).await.unwrap();
+    } else if status.is_server_error() {
+        sync.metrics_sender.send(Metrics::new50x()).await.unwrap();
+    } else {
+        println!(
+            "http error: {} {} {}",
+            url.path(),
+            status,
+            String::from_utf8_lossy(&body)
+        );
+        sync.metrics_sender
+            .send(Metrics::new_other())
+            .await
+            .unwrap();
+    }
+
+    if status.is_success() {
+        serde_json::from_slice(&body).map_err(|e| ApiError::Decode(e.to_string()))
+    } else {
+        Err(ApiError::from_response(status, &body))
+    }
+}
+
+async fn send(
+    url: &Url,
+    timeout: Duration,
+    payload: &serde_json::Value,
+    client: &surf::Client,
+) -> Result<(StatusCode, Vec<u8>), ApiError> {
+    let body = surf::Body::from_json(payload).unwrap();
+    match future::timeout(timeout, client.post(url.as_str()).body(body)).await {
+        Ok(res) => match res {
+            Ok(mut response) => match response.body_bytes().await {
+                Ok(body) => Ok((response.status(), body)),
+                Err(e) => Err(ApiError::Transport(e.to_string())),
+            },
+            Err(e) => Err(ApiError::Transport(e.to_string())),
+        },
+        Err(_) => Err(ApiError::Timeout),
+    }
+}
+
+async fn fetch(
+    url: &Url,
+    timeout: Duration,
+    client: &surf::Client,
+) -> Result<(StatusCode, Vec<u8>), ApiError> {
+    match future::timeout(timeout, client.get(url.as_str())).await {
+        Ok(res) => match res {
+            Ok(mut response) => match response.body_bytes().await {
+                Ok(body) => Ok((response.status(), body)),
+                Err(e) => Err(ApiError::Transport(e.to_string())),
+            },
+            Err(e) => Err(ApiError::Transport(e.to_string())),
+        },
+        Err(_) => Err(ApiError::Timeout),
+    }
+}
@@ -0,0 +1,242 @@
+use crate::http::ApiError;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, LineWriter, Write};
+use std::sync::Mutex;
+use std::time::Duration;
+use surf::http::convert::{Deserialize, Serialize};
+use surf::StatusCode;
+use url::Url;
+
+// one line of the record log; transport failures have status 0, the error
+// kind ("timeout" or "transport") and the error detail in the body.
+// get requests are logged with a null payload
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Exchange {
+    pub url: String,
+    pub payload: serde_json::Value,
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub latency_ms: u64,
+    pub body: String,
+}
+
+pub struct Recorder {
+    writer: Mutex<LineWriter<File>>,
+}
+
+impl Recorder {
+    pub fn create(path: &str) -> Result<Recorder, std::io::Error> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Recorder {
+            writer: Mutex::new(LineWriter::new(file)),
+        })
+    }
+
+    pub fn write(
+        &self,
+        url: &Url,
+        payload: &serde_json::Value,
+        result: &Result<(StatusCode, Vec<u8>), ApiError>,
+        latency: Duration,
+    ) {
+        let (status, error, body) = match result {
+            Ok((status, body)) => (
+                u16::from(*status),
+                None,
+                String::from_utf8_lossy(body).to_string(),
+            ),
+            Err(ApiError::Timeout) => (0, Some("timeout".to_string()), String::new()),
+            Err(ApiError::Transport(detail)) => (0, Some("transport".to_string()), detail.clone()),
+            Err(e) => (0, Some("transport".to_string()), e.to_string()),
+        };
+        let exchange = Exchange {
+            url: url.to_string(),
+            payload: payload.clone(),
+            status,
+            error,
+            latency_ms: latency.as_millis() as u64,
+            body,
+        };
+        let line = serde_json::to_string(&exchange).unwrap();
+        if let Err(e) = writeln!(self.writer.lock().unwrap(), "{}", line) {
+            println!("recorder error: {}", e);
+        }
+    }
+}
+
+// replays recorded responses in order, per endpoint and payload, so
+// concurrently running workers get the answers they got during the run
+pub struct Replayer {
+    exchanges: Mutex<HashMap<String, VecDeque<Exchange>>>,
+}
+
+impl Replayer {
+    pub fn open(path: &str) -> Result<Replayer, std::io::Error> {
+        let mut exchanges: HashMap<String, VecDeque<Exchange>> = HashMap::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let exchange: Exchange = serde_json::from_str(&line)?;
+            let url = Url::parse(&exchange.url)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+            exchanges
+                .entry(key(&url, &exchange.payload))
+                .or_default()
+                .push_back(exchange);
+        }
+        Ok(Replayer {
+            exchanges: Mutex::new(exchanges),
+        })
+    }
+
+    pub fn next(
+        &self,
+        url: &Url,
+        payload: &serde_json::Value,
+    ) -> Result<(StatusCode, Vec<u8>), ApiError> {
+        let exchange = match self
+            .exchanges
+            .lock()
+            .unwrap()
+            .get_mut(&key(url, payload))
+            .and_then(|e| e.pop_front())
+        {
+            Some(e) => e,
+            None => {
+                return Err(ApiError::Transport(format!(
+                    "replay exhausted: {} {}",
+                    url, payload
+                )))
+            }
+        };
+        match (exchange.error.as_deref(), StatusCode::try_from(exchange.status)) {
+            (Some("timeout"), _) => Err(ApiError::Timeout),
+            (None, Ok(status)) => Ok((status, exchange.body.into_bytes())),
+            _ => Err(ApiError::Transport(exchange.body)),
+        }
+    }
+}
+
+fn key(url: &Url, payload: &serde_json::Value) -> String {
+    format!("{} {}", url.path(), payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::context::SyncContext;
+    use crate::http::{http_get, http_post, ApiError};
+    use crate::http::record::{Recorder, Replayer};
+    use crate::mock::world::World;
+    use crate::mock::MockServer;
+    use crate::model::{Area, Dig, License, MoneyList, Tile, TreasureList};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use url::Url;
+
+    // explores the whole world, digs every found point with free licenses
+    // and returns everything the server answered, the balance last
+    async fn run(url: &Url, sync: SyncContext) -> Vec<String> {
+        let timeout = Duration::from_millis(1000);
+        let client = surf::Client::new();
+        let mut log = vec![];
+        for y in 0..8 {
+            for x in 0..8 {
+                let area = Area::new(x, y, 1, 1);
+                let tile: Tile = http_post(
+                    &url.join("explore").unwrap(),
+                    timeout,
+                    area,
+                    &client,
+                    sync.clone(),
+                )
+                .await
+                .unwrap();
+                log.push(tile.to_string());
+                if tile.amount == 0 {
+                    continue;
+                }
+                let license: License = http_post(
+                    &url.join("licenses").unwrap(),
+                    timeout,
+                    MoneyList::new(),
+                    &client,
+                    sync.clone(),
+                )
+                .await
+                .unwrap();
+                let mut dig = Dig::from_tile(tile, license.id);
+                while dig.depth <= 3 {
+                    let result: Result<TreasureList, _> = http_post(
+                        &url.join("dig").unwrap(),
+                        timeout,
+                        dig,
+                        &client,
+                        sync.clone(),
+                    )
+                    .await;
+                    log.push(match result {
+                        Ok(t) => t.0.join(","),
+                        Err(e) => e.to_string(),
+                    });
+                    dig.depth += 1;
+                }
+            }
+        }
+        let balance = http_get(&url.join("balance").unwrap(), timeout, &client, sync).await;
+        log.push(balance.unwrap());
+        log
+    }
+
+    #[async_std::test]
+    async fn test_record_and_replay() {
+        let config = Config::for_tests();
+        let path = std::env::temp_dir().join(format!("hl21-record-{}.log", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let listener = MockServer::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        let mock = MockServer::from_world(World::new(8, 10, 20, 64, 5));
+        async_std::task::spawn(async move { mock.listen(listener).await });
+
+        let mut recorded = SyncContext::new(config);
+        recorded.recorder = Some(Arc::new(Recorder::create(path).unwrap()));
+        let expected = run(&url, recorded).await;
+
+        let mut replayed = SyncContext::new(config);
+        replayed.replayer = Some(Arc::new(Replayer::open(path).unwrap()));
+        let actual = run(&Url::parse("http://replay:1/").unwrap(), replayed).await;
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_replay_transport_error() {
+        let path = std::env::temp_dir().join(format!("hl21-transport-{}.log", std::process::id()));
+        let path = path.to_str().unwrap();
+        let url = Url::parse("http://replay:1/balance").unwrap();
+        let error = Err(ApiError::Transport("connection reset".to_string()));
+        Recorder::create(path).unwrap().write(
+            &url,
+            &serde_json::Value::Null,
+            &error,
+            Duration::from_millis(1),
+        );
+
+        let replayed = Replayer::open(path)
+            .unwrap()
+            .next(&url, &serde_json::Value::Null);
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(
+            replayed.unwrap_err().to_string(),
+            "transport error: connection reset"
+        );
+    }
+}
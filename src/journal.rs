@@ -0,0 +1,115 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, LineWriter, Write};
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::Mutex;
+use surf::http::convert::{DeserializeOwned, Serialize};
+
+// append-only file of json event lines, every event is handed to the os right
+// away so a crashed process loses nothing it has appended
+pub struct Journal<E> {
+    path: String,
+    writer: Mutex<LineWriter<File>>,
+    events: PhantomData<fn(E)>,
+}
+
+impl<E: Serialize + DeserializeOwned> Journal<E> {
+    // replays the journal through `replay`, rewrites it with the events
+    // `compact` makes of the replayed state and opens it for appending
+    pub fn open<S>(
+        path: &str,
+        replay: impl FnOnce(Vec<E>) -> S,
+        compact: impl FnOnce(&S) -> Vec<E>,
+    ) -> Result<(Journal<E>, S), std::io::Error> {
+        let events = if Path::new(path).exists() {
+            read(BufReader::new(File::open(path)?))?
+        } else {
+            vec![]
+        };
+        let state = replay(events);
+
+        let compacted = format!("{}.tmp", path);
+        {
+            let mut file = File::create(&compacted)?;
+            for event in compact(&state) {
+                writeln!(file, "{}", serde_json::to_string(&event).unwrap())?;
+            }
+            file.sync_all()?;
+        }
+        fs::rename(&compacted, path)?;
+
+        let file = OpenOptions::new().append(true).open(path)?;
+        let journal = Journal {
+            path: path.to_string(),
+            writer: Mutex::new(LineWriter::new(file)),
+            events: PhantomData,
+        };
+        Ok((journal, state))
+    }
+
+    pub fn append(&self, event: &E) {
+        let line = serde_json::to_string(event).unwrap();
+        if let Err(e) = writeln!(self.writer.lock().unwrap(), "{}", line) {
+            println!("{} error: {}", self.path, e);
+        }
+    }
+}
+
+fn read<E: DeserializeOwned>(reader: impl BufRead) -> Result<Vec<E>, std::io::Error> {
+    let mut events = vec![];
+    for line in reader.lines() {
+        // the last line may be cut short by a crash
+        if let Ok(event) = serde_json::from_str(&line?) {
+            events.push(event);
+        }
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::journal::Journal;
+    use std::fs;
+
+    // keeps the distinct events in the order they were first appended
+    fn open(path: &str) -> (Journal<u64>, Vec<u64>) {
+        Journal::open(
+            path,
+            |events: Vec<u64>| {
+                let mut distinct = vec![];
+                for event in events {
+                    if !distinct.contains(&event) {
+                        distinct.push(event);
+                    }
+                }
+                distinct
+            },
+            |events| events.clone(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_journal() {
+        let path = std::env::temp_dir().join(format!("hl21-journal-{}.log", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let (journal, events) = open(path);
+        assert!(events.is_empty());
+        for event in [3, 1, 3, 2] {
+            journal.append(&event);
+        }
+        drop(journal);
+        // a crash in the middle of a line
+        let mut data = fs::read_to_string(path).unwrap();
+        data.push_str("[4");
+        fs::write(path, data).unwrap();
+
+        for _ in 0..2 {
+            let (_, events) = open(path);
+            assert_eq!(events, vec![3, 1, 2]);
+        }
+        assert_eq!(fs::read_to_string(path).unwrap(), "3\n1\n2\n");
+        fs::remove_file(path).unwrap();
+    }
+}
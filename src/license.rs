@@ -0,0 +1,271 @@
+use crate::model::License;
+use async_std::channel::{bounded, unbounded, Receiver, Sender};
+use async_std::task;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// how long the last slot waits after a 409 before it is tried again
+const REJECT_BACKOFF: Duration = Duration::from_millis(100);
+
+// one dig allowed by a license, handed back to the manager with `complete`
+#[derive(Copy, Clone, Debug)]
+pub struct Permit {
+    pub license_id: u64,
+}
+
+pub enum DigOutcome {
+    // the server answered the dig, the license spent one dig
+    Used,
+    // the dig didn't reach the server, the permit can be used again
+    Unused,
+    // the server doesn't know the license anymore (403)
+    Invalid,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LicenseStats {
+    pub active: usize,
+    pub limit: usize,
+    pub permits: usize,
+    pub issued: u64,
+    pub exhausted: u64,
+    pub retired: u64,
+    pub rejected: u64,
+}
+
+// owns active licenses and hands out their digs as permits.
+// attorneys reserve one of `limit` slots before asking for a license, so
+// no more licenses than the server allows are active at once. the limit
+// is lowered to what the server's 409 answers show and grows back up to
+// the configured one while licenses retire without new 409s
+pub struct LicenseManager {
+    active: Mutex<HashMap<u64, License>>,
+    stats: Mutex<LicenseStats>,
+    max_limit: usize,
+    // rejects counted when the last license retired
+    rejected_at_retire: Mutex<u64>,
+    slots_sender: Sender<()>,
+    slots_receiver: Receiver<()>,
+    permits_sender: Sender<Permit>,
+    permits_receiver: Receiver<Permit>,
+}
+
+impl LicenseManager {
+    pub fn new(limit: usize) -> LicenseManager {
+        let (slots_sender, slots_receiver) = bounded(limit);
+        for _ in 0..limit {
+            slots_sender.try_send(()).unwrap();
+        }
+        let (permits_sender, permits_receiver) = unbounded();
+        LicenseManager {
+            active: Mutex::new(HashMap::new()),
+            stats: Mutex::new(LicenseStats {
+                limit,
+                ..LicenseStats::default()
+            }),
+            max_limit: limit,
+            rejected_at_retire: Mutex::new(0),
+            slots_sender,
+            slots_receiver,
+            permits_sender,
+            permits_receiver,
+        }
+    }
+
+    // waits until another license may be active
+    pub async fn reserve(&self) {
+        self.slots_receiver.recv().await.unwrap()
+    }
+
+    // gives back a reserved slot that didn't turn into a license
+    pub async fn release(&self) {
+        self.slots_sender.send(()).await.unwrap()
+    }
+
+    // the server refused a license for a reserved slot (409), so it counts
+    // more active licenses than we do, e.g. ones whose answer timed out.
+    // the slot is dropped and attorneys wait for one of ours to retire
+    pub async fn reject(&self) {
+        {
+            let mut stats = self.stats.lock().unwrap();
+            stats.rejected += 1;
+            if stats.limit > 1 {
+                stats.limit -= 1;
+                return;
+            }
+        }
+        task::sleep(REJECT_BACKOFF).await;
+        self.release().await;
+    }
+
+    // takes a license issued for a reserved slot
+    pub async fn issue(&self, license: License) {
+        let permits = license.dig_allowed.saturating_sub(license.dig_used);
+        if permits == 0 {
+            return self.release().await;
+        }
+        let id = license.id;
+        self.active.lock().unwrap().insert(id, license);
+        self.stats.lock().unwrap().issued += 1;
+        for _ in 0..permits {
+            self.permits_sender
+                .send(Permit { license_id: id })
+                .await
+                .unwrap();
+        }
+    }
+
+    pub async fn acquire(&self) -> Permit {
+        loop {
+            let permit = self.permits_receiver.recv().await.unwrap();
+            // permits of retired licenses are dropped here
+            if self.active.lock().unwrap().contains_key(&permit.license_id) {
+                return permit;
+            }
+        }
+    }
+
+    pub async fn complete(&self, permit: Permit, outcome: DigOutcome) {
+        let freed = {
+            let mut active = self.active.lock().unwrap();
+            let license = match active.get_mut(&permit.license_id) {
+                Some(license) => license,
+                None => return,
+            };
+            match outcome {
+                DigOutcome::Used => {
+                    license.dig_used += 1;
+                    if license.dig_used >= license.dig_allowed {
+                        active.remove(&permit.license_id);
+                        self.stats.lock().unwrap().exhausted += 1;
+                        true
+                    } else {
+                        false
+                    }
+                }
+                DigOutcome::Unused => false,
+                DigOutcome::Invalid => {
+                    active.remove(&permit.license_id);
+                    self.stats.lock().unwrap().retired += 1;
+                    true
+                }
+            }
+        };
+        match outcome {
+            DigOutcome::Unused => self.permits_sender.send(permit).await.unwrap(),
+            _ if freed => {
+                self.release().await;
+                if self.grow() {
+                    self.release().await
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // a license retired, the limit takes one more slot if no 409 came
+    // since the previous one retired
+    fn grow(&self) -> bool {
+        let mut stats = self.stats.lock().unwrap();
+        let mut rejected_at_retire = self.rejected_at_retire.lock().unwrap();
+        let calm = *rejected_at_retire == stats.rejected;
+        *rejected_at_retire = stats.rejected;
+        if calm && stats.limit < self.max_limit {
+            stats.limit += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn stats(&self) -> LicenseStats {
+        let mut stats = *self.stats.lock().unwrap();
+        stats.active = self.active.lock().unwrap().len();
+        stats.permits = self.permits_receiver.len();
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::license::{DigOutcome, LicenseManager};
+    use crate::model::License;
+
+    fn license(id: u64, dig_allowed: u64) -> License {
+        License {
+            dig_allowed,
+            dig_used: 0,
+            id,
+        }
+    }
+
+    #[async_std::test]
+    async fn test_license_manager() {
+        let manager = LicenseManager::new(2);
+        manager.reserve().await;
+        manager.issue(license(1, 2)).await;
+        assert_eq!(manager.stats().permits, 2);
+
+        // a failed dig keeps the permit, two answered digs exhaust the license
+        let permit = manager.acquire().await;
+        manager.complete(permit, DigOutcome::Unused).await;
+        for _ in 0..2 {
+            let permit = manager.acquire().await;
+            assert_eq!(permit.license_id, 1);
+            manager.complete(permit, DigOutcome::Used).await;
+        }
+        let stats = manager.stats();
+        assert_eq!((stats.active, stats.exhausted, stats.permits), (0, 1, 0));
+
+        manager.reserve().await;
+        manager.issue(license(2, 3)).await;
+        manager.reserve().await;
+        manager.issue(license(3, 1)).await;
+        assert!(manager.slots_receiver.is_empty());
+
+        // 403 retires license 2 and drops its other permits
+        let permit = manager.acquire().await;
+        assert_eq!(permit.license_id, 2);
+        manager.complete(permit, DigOutcome::Invalid).await;
+        assert!(!manager.slots_receiver.is_empty());
+        assert_eq!(manager.acquire().await.license_id, 3);
+        let stats = manager.stats();
+        assert_eq!((stats.active, stats.retired, stats.issued), (1, 1, 3));
+    }
+
+    #[async_std::test]
+    async fn test_license_limit_from_409() {
+        let manager = LicenseManager::new(3);
+        manager.reserve().await;
+        manager.issue(license(1, 1)).await;
+
+        // the server allows 1, both other slots are dropped
+        for _ in 0..2 {
+            manager.reserve().await;
+            manager.reject().await;
+        }
+        let stats = manager.stats();
+        assert_eq!((stats.limit, stats.rejected), (1, 2));
+        assert!(manager.slots_receiver.is_empty());
+
+        // license 1 retires and frees the only slot, the last slot comes
+        // back after a backoff when it is rejected
+        let permit = manager.acquire().await;
+        manager.complete(permit, DigOutcome::Used).await;
+        manager.reserve().await;
+        manager.reject().await;
+        assert_eq!(manager.stats().limit, 1);
+        assert_eq!(manager.slots_receiver.len(), 1);
+
+        // a retire after the 409 keeps the limit, later ones grow it back to 3
+        for (id, limit) in [(2, 1), (3, 2), (4, 3), (5, 3)] {
+            manager.reserve().await;
+            manager.issue(license(id, 1)).await;
+            let permit = manager.acquire().await;
+            manager.complete(permit, DigOutcome::Used).await;
+            assert_eq!(manager.stats().limit, limit);
+            assert_eq!(manager.slots_receiver.len(), limit);
+        }
+    }
+}
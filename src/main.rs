@@ -1,71 +1,205 @@
+mod checkpoint;
+mod client;
 mod config;
 mod context;
 mod http;
+mod journal;
+mod license;
+mod map;
+mod mock;
 mod model;
+mod pricing;
+mod queue;
+mod retry;
+mod search;
+mod value;
+mod wal;
+mod wallet;
 mod workers;
 
+use crate::checkpoint::Checkpoint;
+use crate::client::{Game, GameClient};
 use crate::config::Config;
-use crate::context::SyncContext;
-use crate::model::{Area, Tile};
+use crate::context::{Metrics, SyncContext};
+use crate::http::record::{Recorder, Replayer};
+use crate::mock::MockServer;
+use crate::retry::DeadLetters;
+use crate::search::{new_strategy, SearchStrategy};
+use crate::wal::{Recovered, Wal};
 use crate::workers::accountant::Accountant;
 use crate::workers::attorney::Attorney;
+use crate::workers::banker::Banker;
 use crate::workers::explorer::Explorer;
 use crate::workers::digger::Digger;
 use crate::workers::statist::Statist;
-use async_std::task;
+use crate::workers::watchman::Watchman;
+use async_std::{future, task};
 use envconfig::Envconfig;
+use std::cmp::max;
 use std::io;
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 #[async_std::main]
 async fn main() -> Result<(), io::Error> {
+    let game_start = Instant::now();
     let config: Config = Config::init_from_env().unwrap();
 
     println!("{}", config);
+    if let Err(e) = config.validate() {
+        panic!("config: {}", e);
+    }
+
+    if config.mock_server_enabled {
+        let listener = MockServer::bind(&config.get_mock_server_addr()).await?;
+        let mock = MockServer::new(config);
+        task::spawn(async move { mock.listen(listener).await });
+        println!("mock server started on {}", config.get_mock_server_addr());
+    }
 
     let mut context = SyncContext::new(config);
-    context.init().await;
+    if let Some(path) = config.get_http_record_file() {
+        context.recorder = Some(Arc::new(Recorder::create(&path)?));
+    }
+    if let Some(path) = config.get_http_replay_file() {
+        context.replayer = Some(Arc::new(Replayer::open(&path)?));
+    }
+    if let Some(path) = config.get_retry_dead_letter_file() {
+        context.dead_letters = Some(Arc::new(DeadLetters::create(&path)?));
+    }
+    let recovered = match config.get_wal_file() {
+        Some(path) => {
+            let (wal, recovered) = Wal::open(&path)?;
+            context.wal = Some(Arc::new(wal));
+            println!(
+                "wal recovered {} treasures, {} coins",
+                recovered.treasures.len(),
+                recovered.coins.len()
+            );
+            Some(recovered)
+        }
+        None => None,
+    };
+    let mut resumed = match config.get_checkpoint_file() {
+        Some(path) => {
+            let (checkpoint, resumed) = Checkpoint::open(&path, config.digger_max_depth)?;
+            context.checkpoint = Some(Arc::new(checkpoint));
+            println!(
+                "checkpoint resumed {} searched seeds, {} points to dig",
+                resumed.searched.len(),
+                resumed.points.len()
+            );
+            Some(resumed)
+        }
+        None => None,
+    };
 
     let statist = Statist::new(config.statist_display_tick, context.clone());
     task::spawn(async move { statist.start().await });
 
+    let game: Arc<dyn Game> = Arc::new(GameClient::new(&config, context.clone()));
+
+    let watchman = Watchman::new(
+        game.clone(),
+        Duration::from_millis(config.health_check_backoff_ms),
+        Duration::from_millis(config.health_check_max_backoff_ms),
+        Duration::from_millis(config.health_check_deadline_ms),
+        context.clone(),
+    );
+    let waited = watchman.wait().await.map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("server is not up after {}ms: {}", config.health_check_deadline_ms, e),
+        )
+    })?;
+    println!("server is up after {}ms", waited.as_millis());
+
+    let banker = Banker::new(
+        game.clone(),
+        Duration::from_secs(config.banker_period_sec),
+        config.banker_reseed,
+        Duration::from_millis(config.health_check_deadline_ms),
+        context.clone(),
+    );
+    banker.reseed().await.map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("no balance to reseed after {}ms: {}", config.health_check_deadline_ms, e),
+        )
+    })?;
+    if let Some(Recovered { treasures, coins }) = recovered {
+        // the reseed already deposited every coin the server holds into the wallet
+        if !config.banker_reseed && coins.len() > 0 {
+            context
+                .metrics_sender
+                .send(Metrics::new_coins_reseeded(coins.len() as u64))
+                .await
+                .unwrap();
+            context.wallet.deposit(coins);
+        }
+        let ctx = context.clone();
+        task::spawn(async move {
+            for treasure in treasures {
+                ctx.treasures.push(treasure).await;
+            }
+        });
+    }
+    if config.banker_period_sec > 0 {
+        task::spawn(async move { banker.start().await });
+    }
+
+    let strategy: Arc<dyn SearchStrategy> = Arc::from(new_strategy(&config));
     let ctx = context.clone();
+    let seeds = strategy.seeds(config.world_size);
 
     task::spawn(async move {
-        for y in 0..config.world_size {
-            for x in (0..config.world_size - config.search_initial_array_size)
-                .into_iter()
-                .step_by(config.search_initial_array_size as usize)
-            {
-                let tile = Tile {
-                    amount: 0,
-                    area: Area::new(x, y, config.search_initial_array_size, 1),
-                };
-                ctx.area_sender.send(tile).await.unwrap();
+        if let Some(resumed) = resumed.as_mut() {
+            for point in resumed.points.drain(..) {
+                let area = &point.tile.area;
+                ctx.map
+                    .write()
+                    .unwrap()
+                    .record_dig(area.pos_x, area.pos_y, point.depth, 0, false);
+                ctx.dig_queue.push(point.tile, point.depth).await;
             }
         }
+        for tile in seeds {
+            if !ctx.is_running() {
+                return;
+            }
+            if matches!(&resumed, Some(r) if r.is_searched(&tile.area)) {
+                continue;
+            }
+            ctx.area_sender.send(tile).await.unwrap();
+        }
     });
 
     for _ in 0..config.search_explorers_num {
-        let context = context.clone();
-        let binary_explorer = Explorer::new(
-            config.get_explore_url(),
-            Duration::from_millis(config.http_timeout_ms),
-            config.search_min_amount,
-            config.search_binary_enabled,
-            config.search_to_flat_threshold,
-            config.search_flat_size,
+        let explorer = Explorer::new(
+            game.clone(),
+            strategy.clone(),
             context.clone(),
         );
-        task::spawn(async move { binary_explorer.start().await });
+        task::spawn(async move { explorer.start().await });
     }
 
+    // found tiles wait in the tile channel for a slot of the dig queue
+    let ctx = context.clone();
+    task::spawn(async move {
+        loop {
+            let tile = ctx.tile_receiver.recv().await.unwrap();
+            let depth = ctx
+                .map
+                .read()
+                .unwrap()
+                .depth(tile.area.pos_x, tile.area.pos_y);
+            ctx.dig_queue.push(tile, depth).await;
+        }
+    });
+
     for _ in 0..config.attorneys_num {
         let attorney = Attorney::new(
-            config.get_licenses_url(),
-            Duration::from_millis(config.attorney_http_timeout_ms),
-            config.attorney_license_min_cost,
-            config.attorney_license_max_cost,
+            game.clone(),
             config.attorney_free_license_probability,
             context.clone(),
         );
@@ -74,30 +208,57 @@ async fn main() -> Result<(), io::Error> {
 
     for _ in 0..config.diggers_num {
         let digger = Digger::new(
-            config.get_dig_url(),
-            Duration::from_millis(config.http_timeout_ms),
+            game.clone(),
             config.digger_min_depth,
             config.digger_max_depth,
-            config.digger_min_depth_probability,
+            config.digger_points_in_flight,
             context.clone(),
         );
         task::spawn(async move { digger.start().await });
     }
 
     for _ in 0..config.accountant_num {
-        let accountant = Accountant::new(
-            config.get_cash_url(),
-            Duration::from_millis(config.accountant_http_timeout_ms),
-            context.clone(),
-        );
+        let accountant = Accountant::new(game.clone(), context.clone());
         task::spawn(async move { accountant.start().await });
     }
 
-    loop {
-        task::sleep(Duration::from_secs(config.phase2_start)).await;
-        if config.enable_phased {
-            context.switch_phase();
-            println!("phase switched")
+    let mut ctx = context.clone();
+    task::spawn(async move {
+        loop {
+            task::sleep(Duration::from_secs(config.phase2_start)).await;
+            if config.enable_phased && ctx.is_running() {
+                ctx.switch_phase();
+                println!("phase switched")
+            }
         }
+    });
+
+    if config.game_duration_sec == 0 {
+        future::pending::<()>().await;
     }
+
+    let deadline = Duration::from_secs(config.game_duration_sec);
+    let drain_start = deadline - Duration::from_secs(config.game_drain_sec).min(deadline);
+    task::sleep(drain_start.saturating_sub(game_start.elapsed())).await;
+    context.start_drain();
+    println!("draining before the deadline");
+
+    task::sleep(deadline.saturating_sub(game_start.elapsed())).await;
+    context.finish_game();
+    let longest_timeout = Duration::from_millis(max(
+        config.http_timeout_ms,
+        max(
+            config.attorney_http_timeout_ms,
+            config.accountant_http_timeout_ms,
+        ),
+    ));
+    let stop = Instant::now();
+    while context.in_flight() > 0 && stop.elapsed() < longest_timeout {
+        task::sleep(Duration::from_millis(5)).await;
+    }
+
+    Statist::new(config.statist_display_tick, context.clone())
+        .summary(game_start.elapsed())
+        .await;
+    Ok(())
 }
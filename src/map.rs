@@ -0,0 +1,187 @@
+use crate::model::Area;
+use std::cmp::max;
+use std::collections::HashMap;
+
+// explored areas kept before the first sweep of the dug out ones
+const SWEEP_MIN_AREAS: usize = 1024;
+
+// what the bot has learned about the world: explored areas, cells known to be
+// empty and how deep every point has been dug
+pub struct WorldMap {
+    size: u64,
+    empty: Vec<u64>,
+    depth: Vec<u8>,
+    // treasures dug out of a cell
+    taken: Vec<u8>,
+    // explored area -> (amount, treasures already taken from it at that time)
+    areas: HashMap<(u64, u64, u64, u64), (u64, u64)>,
+    // number of areas that triggers the next sweep
+    sweep_at: usize,
+}
+
+impl WorldMap {
+    pub fn new(size: u64) -> WorldMap {
+        let cells = (size * size) as usize;
+        WorldMap {
+            size,
+            empty: vec![0; cells.div_ceil(64)],
+            depth: vec![0; cells],
+            taken: vec![0; cells],
+            areas: HashMap::new(),
+            sweep_at: SWEEP_MIN_AREAS,
+        }
+    }
+
+    fn index(&self, x: u64, y: u64) -> usize {
+        (y * self.size + x) as usize
+    }
+
+    fn contains(&self, area: &Area) -> bool {
+        area.pos_x + area.size_x <= self.size && area.pos_y + area.size_y <= self.size
+    }
+
+    fn cells(&self, area: &Area) -> impl Iterator<Item = usize> + '_ {
+        let (pos_x, size_x, size) = (area.pos_x, area.size_x, self.size);
+        (area.pos_y..area.pos_y + area.size_y)
+            .flat_map(move |y| (pos_x..pos_x + size_x).map(move |x| (y * size + x) as usize))
+    }
+
+    fn is_empty_cell(&self, i: usize) -> bool {
+        self.empty[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    fn taken(&self, area: &Area) -> u64 {
+        self.cells(area).map(|i| self.taken[i] as u64).sum()
+    }
+
+    // `amount` and `taken` - what the area held when it was explored
+    fn left(&self, area: &Area, amount: u64, taken: u64) -> u64 {
+        amount.saturating_sub(self.taken(area).saturating_sub(taken))
+    }
+
+    // dug out areas turn into empty cells, so the areas map only keeps the
+    // ones with treasures left. runs once the map has doubled since the last
+    // sweep, so it costs explores next to nothing
+    fn sweep(&mut self) {
+        let dug_out: Vec<Area> = self
+            .areas
+            .iter()
+            .map(|(&(pos_x, pos_y, size_x, size_y), &amount)| {
+                (Area::new(pos_x, pos_y, size_x, size_y), amount)
+            })
+            .filter(|(area, (amount, taken))| self.left(area, *amount, *taken) == 0)
+            .map(|(area, _)| area)
+            .collect();
+        for area in dug_out {
+            for i in self.cells(&area).collect::<Vec<usize>>() {
+                self.empty[i / 64] |= 1 << (i % 64);
+            }
+            self.areas.remove(&(area.pos_x, area.pos_y, area.size_x, area.size_y));
+        }
+        self.sweep_at = max(SWEEP_MIN_AREAS, self.areas.len() * 2);
+    }
+
+    // treasures left in an area, if it is known without exploring it
+    pub fn known(&self, area: &Area) -> Option<u64> {
+        if !self.contains(area) {
+            return None;
+        }
+        let key = (area.pos_x, area.pos_y, area.size_x, area.size_y);
+        if let Some((amount, taken)) = self.areas.get(&key) {
+            return Some(self.left(area, *amount, *taken));
+        }
+        if self.cells(area).all(|i| self.is_empty_cell(i)) {
+            return Some(0);
+        }
+        None
+    }
+
+    pub fn record_explore(&mut self, area: &Area, amount: u64) {
+        if !self.contains(area) {
+            return;
+        }
+        if amount == 0 {
+            for i in self.cells(area).collect::<Vec<usize>>() {
+                self.empty[i / 64] |= 1 << (i % 64);
+            }
+        } else {
+            if self.areas.len() >= self.sweep_at {
+                self.sweep();
+            }
+            let taken = self.taken(area);
+            self.areas.insert(
+                (area.pos_x, area.pos_y, area.size_x, area.size_y),
+                (amount, taken),
+            );
+        }
+    }
+
+    // deepest level dug at a point, 0 if it was never dug
+    pub fn depth(&self, x: u64, y: u64) -> u64 {
+        self.depth[self.index(x, y)] as u64
+    }
+
+    pub fn is_exhausted(&self, x: u64, y: u64) -> bool {
+        self.is_empty_cell(self.index(x, y))
+    }
+
+    // `exhausted` - every treasure the point had is dug out
+    pub fn record_dig(&mut self, x: u64, y: u64, depth: u64, found: u64, exhausted: bool) {
+        let i = self.index(x, y);
+        self.depth[i] = self.depth[i].max(depth as u8);
+        self.taken[i] = self.taken[i].saturating_add(found as u8);
+        if exhausted {
+            self.empty[i / 64] |= 1 << (i % 64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::map::WorldMap;
+    use crate::model::Area;
+
+    #[test]
+    fn test_world_map() {
+        let mut map = WorldMap::new(100);
+        let row = Area::new(10, 5, 31, 1);
+        assert_eq!(map.known(&row), None);
+
+        map.record_explore(&Area::new(10, 5, 16, 1), 0);
+        assert_eq!(map.known(&Area::new(12, 5, 3, 1)), Some(0));
+        assert_eq!(map.known(&row), None);
+
+        map.record_explore(&row, 3);
+        assert_eq!(map.known(&row), Some(3));
+        map.record_dig(30, 5, 1, 2, false);
+        assert_eq!(map.depth(30, 5), 1);
+        assert_eq!(map.known(&row), Some(1));
+        assert!(!map.is_exhausted(30, 5));
+        map.record_dig(30, 5, 2, 1, true);
+        assert_eq!(map.known(&row), Some(0));
+        assert!(map.is_exhausted(30, 5));
+        assert_eq!(map.known(&Area::new(30, 5, 1, 1)), Some(0));
+        // more dug out than the explore counted
+        map.record_dig(29, 5, 1, 2, false);
+        assert_eq!(map.known(&row), Some(0));
+        assert_eq!(map.known(&Area::new(99, 99, 2, 1)), None);
+    }
+
+    #[test]
+    fn test_world_map_sweep() {
+        let mut map = WorldMap::new(100);
+        for i in 0..1024 {
+            map.record_explore(&Area::new(i % 100, i / 100, 1, 1), 1);
+            if i < 1000 {
+                map.record_dig(i % 100, i / 100, 1, 1, false);
+            }
+        }
+        assert_eq!(map.areas.len(), 1024);
+
+        map.record_explore(&Area::new(0, 20, 1, 1), 1);
+        assert_eq!(map.areas.len(), 25);
+        assert_eq!(map.known(&Area::new(0, 0, 100, 10)), Some(0));
+        assert_eq!(map.known(&Area::new(0, 10, 1, 1)), Some(1));
+        assert_eq!(map.known(&Area::new(0, 20, 1, 1)), Some(1));
+    }
+}
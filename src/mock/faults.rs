@@ -0,0 +1,117 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Mutex;
+use std::time::Duration;
+use surf::http::convert::Deserialize;
+
+const ANY_ENDPOINT: &str = "*";
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct EndpointFaults {
+    // uniform latency range added before the request is handled
+    pub latency_ms: (u64, u64),
+    // rare long stalls on top of latency_ms, enough to trip client timeouts
+    pub latency_spike_rate: f64,
+    pub latency_spike_ms: u64,
+    pub error_429_rate: f64,
+    pub error_5xx_rate: f64,
+    pub reset_rate: f64,
+}
+
+pub enum Fault {
+    None,
+    Status(u16),
+    Reset,
+}
+
+// scenario file is a json object keyed by endpoint name ("explore", "dig",
+// "licenses", "cash", "balance", "health-check") with "*" as a fallback
+pub struct FaultInjector {
+    scenario: HashMap<String, EndpointFaults>,
+    rng: Mutex<StdRng>,
+}
+
+impl FaultInjector {
+    pub fn new(scenario: HashMap<String, EndpointFaults>, seed: u64) -> FaultInjector {
+        FaultInjector {
+            scenario,
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+        }
+    }
+
+    pub fn from_file(path: &str, seed: u64) -> Result<FaultInjector, String> {
+        let data = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        let scenario = serde_json::from_slice(&data).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(FaultInjector::new(scenario, seed))
+    }
+
+    fn endpoint(&self, path: &str) -> Option<&EndpointFaults> {
+        self.scenario
+            .get(path.trim_start_matches('/'))
+            .or_else(|| self.scenario.get(ANY_ENDPOINT))
+    }
+
+    pub fn latency(&self, path: &str) -> Duration {
+        let faults = match self.endpoint(path) {
+            Some(f) => f,
+            None => return Duration::from_millis(0),
+        };
+        let mut rng = self.rng.lock().unwrap();
+        let (min, max) = faults.latency_ms;
+        let mut latency = if max > min {
+            rng.gen_range(min..=max)
+        } else {
+            min
+        };
+        if rng.gen_bool(faults.latency_spike_rate.clamp(0.0, 1.0)) {
+            latency += faults.latency_spike_ms;
+        }
+        Duration::from_millis(latency)
+    }
+
+    pub fn fault(&self, path: &str) -> Fault {
+        let faults = match self.endpoint(path) {
+            Some(f) => f,
+            None => return Fault::None,
+        };
+        let roll: f64 = self.rng.lock().unwrap().gen();
+        if roll < faults.reset_rate {
+            Fault::Reset
+        } else if roll < faults.reset_rate + faults.error_429_rate {
+            Fault::Status(429)
+        } else if roll < faults.reset_rate + faults.error_429_rate + faults.error_5xx_rate {
+            Fault::Status(503)
+        } else {
+            Fault::None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock::faults::{Fault, FaultInjector};
+    use std::time::Duration;
+
+    #[test]
+    fn test_scenario() {
+        let scenario = serde_json::from_str(
+            r#"{
+                "dig": {"error_5xx_rate": 1.0},
+                "cash": {"reset_rate": 1.0, "latency_ms": [20, 20]},
+                "*": {"latency_ms": [1, 5], "latency_spike_rate": 1.0, "latency_spike_ms": 100}
+            }"#,
+        )
+        .unwrap();
+        let faults = FaultInjector::new(scenario, 1);
+
+        assert!(matches!(faults.fault("/dig"), Fault::Status(503)));
+        assert!(matches!(faults.fault("/cash"), Fault::Reset));
+        assert!(matches!(faults.fault("/explore"), Fault::None));
+        assert_eq!(faults.latency("/cash"), Duration::from_millis(20));
+        let latency = faults.latency("/explore");
+        assert!(latency >= Duration::from_millis(101) && latency <= Duration::from_millis(105));
+    }
+}
@@ -0,0 +1,204 @@
+pub mod faults;
+pub mod world;
+
+use crate::config::Config;
+use crate::http::HttpError;
+use crate::mock::faults::{Fault, FaultInjector};
+use crate::mock::world::World;
+use async_std::net::{TcpListener, TcpStream};
+use async_std::task;
+use std::io;
+use std::net::Shutdown;
+use std::sync::{Arc, Mutex};
+use surf::http::convert::{DeserializeOwned, Serialize};
+use surf::http::{Body, Method, Request, Response, StatusCode};
+
+#[derive(Clone)]
+pub struct MockServer {
+    world: Arc<Mutex<World>>,
+    faults: Option<Arc<FaultInjector>>,
+}
+
+impl MockServer {
+    pub fn new(c: Config) -> MockServer {
+        let treasures_num = (c.world_size * c.world_size) as f64 * c.mock_treasure_density;
+        let server = MockServer::from_world(World::new(
+            c.world_size,
+            c.digger_max_depth,
+            treasures_num as u64,
+            c.mock_active_license_limit,
+            c.mock_seed,
+        ));
+        match c.get_mock_faults_file() {
+            Some(path) => server.with_faults(
+                FaultInjector::from_file(&path, c.mock_seed)
+                    .unwrap_or_else(|e| panic!("mock faults scenario: {}", e)),
+            ),
+            None => server,
+        }
+    }
+
+    pub fn from_world(world: World) -> MockServer {
+        MockServer {
+            world: Arc::new(Mutex::new(world)),
+            faults: None,
+        }
+    }
+
+    pub fn with_faults(mut self, faults: FaultInjector) -> MockServer {
+        self.faults = Some(Arc::new(faults));
+        self
+    }
+
+    pub async fn bind(addr: &str) -> Result<TcpListener, io::Error> {
+        TcpListener::bind(addr).await
+    }
+
+    pub async fn listen(self, listener: TcpListener) {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    println!("mock server accept error: {}", e);
+                    continue;
+                }
+            };
+            // async-h1 writes head and body separately, avoid the delayed ack stall
+            let _ = stream.set_nodelay(true);
+            let server = self.clone();
+            task::spawn(async move {
+                let _ = async_h1::accept(stream.clone(), |req| {
+                    server.clone().handle(req, stream.clone())
+                })
+                .await;
+            });
+        }
+    }
+
+    async fn handle(self, mut req: Request, stream: TcpStream) -> surf::http::Result<Response> {
+        let body = req.body_bytes().await?;
+        let path = req.url().path().to_string();
+        if let Some(faults) = &self.faults {
+            task::sleep(faults.latency(&path)).await;
+            match faults.fault(&path) {
+                Fault::None => {}
+                Fault::Status(status) => {
+                    let mut response = Response::new(status);
+                    response.set_body(Body::from_json(&HttpError::new(
+                        status,
+                        "Injected fault".to_string(),
+                        path,
+                    ))?);
+                    return Ok(response);
+                }
+                Fault::Reset => {
+                    stream.shutdown(Shutdown::Both)?;
+                    return Err(surf::http::Error::from_str(
+                        StatusCode::InternalServerError,
+                        "injected connection reset",
+                    ));
+                }
+            }
+        }
+        let result = match (req.method(), path.as_str()) {
+            (Method::Post, "/explore") => {
+                parse(&body).and_then(|area| reply(self.world.lock().unwrap().explore(area)))
+            }
+            (Method::Post, "/licenses") => parse(&body)
+                .and_then(|coins| reply(self.world.lock().unwrap().issue_license(coins))),
+            (Method::Post, "/dig") => {
+                parse(&body).and_then(|dig| reply(self.world.lock().unwrap().dig(dig)))
+            }
+            (Method::Post, "/cash") => {
+                parse(&body).and_then(|treasure| reply(self.world.lock().unwrap().cash(treasure)))
+            }
+            (Method::Get, "/balance") => reply(Ok(self.world.lock().unwrap().balance())),
+            (Method::Get, "/health-check") => Ok(Body::from_string("{}".to_string())),
+            _ => Err(HttpError::new(
+                404,
+                "Not Found".to_string(),
+                format!("no route: {}", path),
+            )),
+        };
+        Ok(match result {
+            Ok(body) => {
+                let mut response = Response::new(StatusCode::Ok);
+                response.set_body(body);
+                response
+            }
+            Err(e) => {
+                let mut response = Response::new(e.status);
+                response.set_body(Body::from_json(&e)?);
+                response
+            }
+        })
+    }
+}
+
+fn parse<T: DeserializeOwned>(body: &[u8]) -> Result<T, HttpError> {
+    serde_json::from_slice(body)
+        .map_err(|e| HttpError::new(400, "Bad Request".to_string(), e.to_string()))
+}
+
+fn reply<T: Serialize>(result: Result<T, HttpError>) -> Result<Body, HttpError> {
+    result.and_then(|t| {
+        Body::from_json(&t)
+            .map_err(|e| HttpError::new(500, "Internal Server Error".to_string(), e.to_string()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::context::SyncContext;
+    use crate::http::{http_post, ApiError};
+    use crate::mock::faults::{EndpointFaults, FaultInjector};
+    use crate::mock::world::World;
+    use crate::mock::MockServer;
+    use crate::model::{Area, Tile};
+    use std::collections::HashMap;
+    use std::time::Duration;
+    use url::Url;
+
+    #[async_std::test]
+    async fn test_reset_against_http_post() {
+        let mut scenario = HashMap::new();
+        scenario.insert(
+            "dig".to_string(),
+            EndpointFaults {
+                reset_rate: 1.0,
+                ..EndpointFaults::default()
+            },
+        );
+        let listener = MockServer::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mock = MockServer::from_world(World::new(8, 10, 20, 64, 5))
+            .with_faults(FaultInjector::new(scenario, 5));
+        async_std::task::spawn(async move { mock.listen(listener).await });
+
+        let sync = SyncContext::new(Config::for_tests());
+        let client = surf::Client::new();
+        let url = Url::parse(&format!("http://{}/dig", addr)).unwrap();
+        let result: Result<Tile, ApiError> = http_post(
+            &url,
+            Duration::from_millis(1000),
+            Area::new(0, 0, 1, 1),
+            &client,
+            sync.clone(),
+        )
+        .await;
+        assert!(matches!(result, Err(ApiError::Transport(_))));
+
+        // the connection is dropped for the faulty endpoint only
+        let url = Url::parse(&format!("http://{}/explore", addr)).unwrap();
+        let result: Result<Tile, ApiError> = http_post(
+            &url,
+            Duration::from_millis(1000),
+            Area::new(0, 0, 1, 1),
+            &client,
+            sync,
+        )
+        .await;
+        assert_eq!(result.unwrap().area.size_x, 1);
+    }
+}
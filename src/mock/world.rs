@@ -0,0 +1,316 @@
+use crate::http::HttpError;
+use crate::model::{Area, Balance, Dig, License, MoneyList, Tile, TreasureList};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+
+pub const FREE_LICENSE_DIGS: u64 = 3;
+
+pub struct World {
+    size: u64,
+    max_depth: u64,
+    active_license_limit: usize,
+    // treasures left per cell, used to answer explore without walking the map
+    cells: Vec<u8>,
+    // depths of the treasures left in a cell
+    treasures: HashMap<(u64, u64), Vec<u64>>,
+    dug: HashMap<(u64, u64), u64>,
+    licenses: HashMap<u64, License>,
+    next_license_id: u64,
+    // dug but not cashed treasures with their depth
+    issued: HashMap<String, u64>,
+    next_treasure_id: u64,
+    wallet: Vec<u32>,
+    next_coin: u32,
+    rng: StdRng,
+}
+
+impl World {
+    pub fn new(
+        size: u64,
+        max_depth: u64,
+        treasures_num: u64,
+        active_license_limit: usize,
+        seed: u64,
+    ) -> World {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut cells = vec![0u8; (size * size) as usize];
+        let mut treasures: HashMap<(u64, u64), Vec<u64>> = HashMap::new();
+        let mut placed = 0;
+        while placed < treasures_num {
+            let x = rng.gen_range(0..size);
+            let y = rng.gen_range(0..size);
+            let cell = &mut cells[(y * size + x) as usize];
+            if *cell == u8::MAX {
+                continue;
+            }
+            *cell += 1;
+            treasures
+                .entry((x, y))
+                .or_default()
+                .push(rng.gen_range(1..=max_depth));
+            placed += 1;
+        }
+        World {
+            size,
+            max_depth,
+            active_license_limit,
+            cells,
+            treasures,
+            dug: HashMap::new(),
+            licenses: HashMap::new(),
+            next_license_id: 1,
+            issued: HashMap::new(),
+            next_treasure_id: 1,
+            wallet: vec![],
+            next_coin: 1,
+            rng,
+        }
+    }
+
+    pub fn explore(&self, area: Area) -> Result<Tile, HttpError> {
+        if area.size_x == 0
+            || area.size_y == 0
+            || area.pos_x + area.size_x > self.size
+            || area.pos_y + area.size_y > self.size
+        {
+            return Err(bad_request(format!("wrong area: {}", area)));
+        }
+        let mut amount = 0u64;
+        for y in area.pos_y..area.pos_y + area.size_y {
+            let row = (y * self.size) as usize;
+            for x in area.pos_x..area.pos_x + area.size_x {
+                amount += self.cells[row + x as usize] as u64;
+            }
+        }
+        Ok(Tile { amount, area })
+    }
+
+    pub fn issue_license(&mut self, coins: MoneyList) -> Result<License, HttpError> {
+        if self.licenses.len() >= self.active_license_limit {
+            return Err(HttpError::new(
+                409,
+                "Conflict".to_string(),
+                "no more active licenses allowed".to_string(),
+            ));
+        }
+        let coins: Vec<u32> = coins.iter().copied().collect();
+        if coins.iter().any(|c| !self.wallet.contains(c)) {
+            return Err(HttpError::new(
+                402,
+                "Payment Required".to_string(),
+                "coin is not in the wallet".to_string(),
+            ));
+        }
+        self.wallet.retain(|c| !coins.contains(c));
+        let license = License {
+            dig_allowed: dig_allowed(coins.len()),
+            dig_used: 0,
+            id: self.next_license_id,
+        };
+        self.next_license_id += 1;
+        self.licenses.insert(
+            license.id,
+            License {
+                dig_allowed: license.dig_allowed,
+                dig_used: 0,
+                id: license.id,
+            },
+        );
+        Ok(license)
+    }
+
+    pub fn dig(&mut self, dig: Dig) -> Result<TreasureList, HttpError> {
+        let license = match self.licenses.get_mut(&dig.license_id) {
+            Some(l) => l,
+            None => {
+                return Err(HttpError::new(
+                    403,
+                    "Forbidden".to_string(),
+                    format!("no such license: {}", dig.license_id),
+                ))
+            }
+        };
+        if dig.pos_x >= self.size || dig.pos_y >= self.size || dig.depth > self.max_depth {
+            return Err(bad_request(format!(
+                "wrong coordinates: {}:{}:{}",
+                dig.pos_x, dig.pos_y, dig.depth
+            )));
+        }
+        let point = (dig.pos_x, dig.pos_y);
+        let reached = self.dug.get(&point).copied().unwrap_or(0);
+        if dig.depth != reached + 1 {
+            return Err(bad_request(format!(
+                "wrong depth: {}, expected {}",
+                dig.depth,
+                reached + 1
+            )));
+        }
+
+        license.dig_used += 1;
+        if license.dig_used >= license.dig_allowed {
+            self.licenses.remove(&dig.license_id);
+        }
+        self.dug.insert(point, dig.depth);
+
+        let mut found = TreasureList::new();
+        if let Some(depths) = self.treasures.get_mut(&point) {
+            let before = depths.len();
+            depths.retain(|d| *d != dig.depth);
+            for _ in depths.len()..before {
+                let id = format!("{:x}", self.next_treasure_id);
+                self.next_treasure_id += 1;
+                self.issued.insert(id.clone(), dig.depth);
+                found.0.push(id);
+            }
+            self.cells[(dig.pos_y * self.size + dig.pos_x) as usize] -=
+                (before - depths.len()) as u8;
+        }
+        if found.0.is_empty() {
+            return Err(HttpError::new(
+                404,
+                "Not Found".to_string(),
+                "no treasure".to_string(),
+            ));
+        }
+        Ok(found)
+    }
+
+    pub fn cash(&mut self, treasure: String) -> Result<MoneyList, HttpError> {
+        let depth = match self.issued.remove(&treasure) {
+            Some(d) => d,
+            None => {
+                return Err(HttpError::new(
+                    404,
+                    "Not Found".to_string(),
+                    format!("no such treasure: {}", treasure),
+                ))
+            }
+        };
+        let value = self.rng.gen_range(depth..=depth * 2);
+        let coins: Vec<u32> = (0..value as u32).map(|i| self.next_coin + i).collect();
+        self.next_coin += value as u32;
+        self.wallet.extend(coins.iter());
+        Ok(coins.iter().collect())
+    }
+
+    pub fn balance(&self) -> Balance {
+        Balance {
+            balance: self.wallet.len() as u32,
+            wallet: self.wallet.clone(),
+        }
+    }
+}
+
+// stepwise price table the attorney tiers (1,6,11,21) are tuned for
+fn dig_allowed(coins: usize) -> u64 {
+    match coins {
+        0 => FREE_LICENSE_DIGS,
+        1..=5 => 5,
+        6..=10 => 10,
+        11..=20 => 20,
+        _ => 40,
+    }
+}
+
+fn bad_request(detail: String) -> HttpError {
+    HttpError::new(422, "Unprocessable Entity".to_string(), detail)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock::world::{World, FREE_LICENSE_DIGS};
+    use crate::model::{Area, Dig, MoneyList};
+
+    fn dig(world: &mut World, license_id: u64, x: u64, y: u64, depth: u64) -> u16 {
+        let dig = Dig {
+            depth,
+            license_id,
+            pos_x: x,
+            pos_y: y,
+            amount: 0,
+        };
+        match world.dig(dig) {
+            Ok(_) => 200,
+            Err(e) => e.status,
+        }
+    }
+
+    #[test]
+    fn test_world_is_seeded() {
+        let a = World::new(64, 10, 200, 10, 7);
+        let b = World::new(64, 10, 200, 10, 7);
+        let whole = Area::new(0, 0, 64, 64);
+        assert_eq!(a.explore(whole.clone()).unwrap().amount, 200);
+        for y in 0..64 {
+            let row = Area::new(0, y, 64, 1);
+            assert_eq!(
+                a.explore(row.clone()).unwrap().amount,
+                b.explore(row).unwrap().amount
+            );
+        }
+        assert_eq!(a.explore(Area::new(60, 0, 5, 1)).err().unwrap().status, 422);
+    }
+
+    #[test]
+    fn test_license_limits() {
+        let mut world = World::new(16, 10, 0, 2, 1);
+        let first = world.issue_license(MoneyList::new()).unwrap();
+        assert_eq!(first.dig_allowed, FREE_LICENSE_DIGS);
+        world.issue_license(MoneyList::new()).unwrap();
+        assert_eq!(
+            world.issue_license(MoneyList::new()).err().unwrap().status,
+            409
+        );
+
+        for depth in 1..=FREE_LICENSE_DIGS {
+            assert_eq!(dig(&mut world, first.id, 0, 0, depth), 404);
+        }
+        assert_eq!(dig(&mut world, first.id, 0, 0, FREE_LICENSE_DIGS + 1), 403);
+        assert_eq!(
+            world
+                .issue_license([1u32].iter().collect())
+                .err()
+                .unwrap()
+                .status,
+            402
+        );
+        world.issue_license(MoneyList::new()).unwrap();
+    }
+
+    #[test]
+    fn test_treasure_depths() {
+        let mut world = World::new(8, 10, 30, 10, 3);
+        let license = world.issue_license(MoneyList::new()).unwrap();
+        let (x, y) = *world.treasures.keys().next().unwrap();
+        let depth = world.treasures[&(x, y)][0];
+        let amount = world.explore(Area::new(x, y, 1, 1)).unwrap().amount;
+
+        assert!(depth >= 1);
+        assert_eq!(dig(&mut world, license.id, x, y, 2), 422);
+        let mut found = 0;
+        let mut license = license;
+        for d in 1..=10 {
+            if d > 1 && (d - 1) % FREE_LICENSE_DIGS == 0 {
+                license = world.issue_license(MoneyList::new()).unwrap();
+            }
+            let dig = Dig {
+                depth: d,
+                license_id: license.id,
+                pos_x: x,
+                pos_y: y,
+                amount: 0,
+            };
+            if let Ok(t) = world.dig(dig) {
+                for id in t.0 {
+                    found += 1;
+                    assert!(world.cash(id.clone()).unwrap().len() as u64 >= d);
+                    assert_eq!(world.cash(id).err().unwrap().status, 404);
+                }
+            }
+        }
+        assert_eq!(found, amount);
+        assert_eq!(world.explore(Area::new(x, y, 1, 1)).unwrap().amount, 0);
+        assert_eq!(world.balance().wallet.len() as u32, world.balance().balance);
+    }
+}
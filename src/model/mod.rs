@@ -4,8 +4,8 @@ use surf::http::convert::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize)]
 pub struct Balance {
-    balance: u32,
-    wallet: Vec<u32>,
+    pub balance: u32,
+    pub wallet: Vec<u32>,
 }
 
 impl Display for Balance {
@@ -61,6 +61,12 @@ pub struct Tile {
 }
 
 impl Tile {
+    pub fn new(pos_x: u64, pos_y: u64, size_x: u64, size_y: u64) -> Tile {
+        Tile {
+            amount: 0,
+            area: Area::new(pos_x, pos_y, size_x, size_y),
+        }
+    }
     pub fn has_treasures(&self, min_num: u64) -> bool {
         self.amount >= min_num
     }
@@ -86,6 +92,42 @@ impl Tile {
         (left, right)
     }
 
+    // splits along X into `parts` tiles whose sizes differ by at most one
+    pub fn split_even(&self, parts: u64) -> Vec<Tile> {
+        let parts = parts.clamp(1, self.area.size_x);
+        let base = self.area.size_x / parts;
+        let wider = self.area.size_x % parts;
+        let mut result = Vec::new();
+        let mut pos_x = self.area.pos_x;
+        for i in 0..parts {
+            let size_x = if i < wider { base + 1 } else { base };
+            result.push(Tile::new(pos_x, self.area.pos_y, size_x, self.area.size_y));
+            pos_x += size_x;
+        }
+        result
+    }
+
+    // splits along both axes, a side of size 1 is left as is
+    pub fn split_quad(&self) -> Vec<Tile> {
+        let half_x = if self.area.size_x > 1 { self.area.size_x / 2 } else { self.area.size_x };
+        let half_y = if self.area.size_y > 1 { self.area.size_y / 2 } else { self.area.size_y };
+        let mut result = Vec::new();
+        for (pos_y, size_y) in [
+            (self.area.pos_y, half_y),
+            (self.area.pos_y + half_y, self.area.size_y - half_y),
+        ] {
+            for (pos_x, size_x) in [
+                (self.area.pos_x, half_x),
+                (self.area.pos_x + half_x, self.area.size_x - half_x),
+            ] {
+                if size_x > 0 && size_y > 0 {
+                    result.push(Tile::new(pos_x, pos_y, size_x, size_y));
+                }
+            }
+        }
+        result
+    }
+
     pub fn split_to_tiles(&self, preferred_tile_size: u64) -> Vec<Tile> {
         let mut result = Vec::new();
         let triples = self.area.size_x / preferred_tile_size;
@@ -165,54 +207,25 @@ pub struct License {
     pub id: u64,
 }
 
-impl License {
-    pub fn new() -> License {
-        License {
-            dig_allowed: 0,
-            dig_used: 0,
-            id: 0,
-        }
-    }
-}
-
-pub struct SplitMoneyList {
-    pub money: MoneyList,
-    pub exchange: MoneyList,
-}
-
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MoneyList(Vec<u32>);
 
 impl MoneyList {
     pub fn new() -> MoneyList {
-        MoneyList { 0: vec![] }
+        MoneyList(vec![])
     }
     pub fn len(&self) -> usize {
         self.0.len()
     }
-    pub fn get_optimal_list(&self, max_cost: usize, min_cost: usize) -> SplitMoneyList {
-        //1,6,11,21
-        let l = self.len();
-        let mut n;
-        if l >= 21 {
-            n = 21;
-        } else if l >= 11 {
-            n = 11;
-        } else if l >= 6 {
-            n = 6;
-        } else {
-            n = 1;
-        }
-        if n < min_cost {
-            n = 0
-        }
-        if n > max_cost {
-            n = max_cost
-        }
-        SplitMoneyList {
-            money: self.0.iter().take(n).collect(),
-            exchange: self.0.iter().rev().take(l - n).collect(),
-        }
+    pub fn iter(&self) -> std::slice::Iter<'_, u32> {
+        self.0.iter()
+    }
+    pub fn extend(&mut self, other: MoneyList) {
+        self.0.extend(other.0)
+    }
+    // takes the first `n` coins out of the list
+    pub fn split_first(&mut self, n: usize) -> MoneyList {
+        MoneyList(self.0.drain(..n.min(self.0.len())).collect())
     }
 }
 
@@ -229,9 +242,18 @@ impl<'a> FromIterator<&'a u32> for MoneyList {
 #[derive(Serialize, Deserialize)]
 pub struct TreasureList(pub Vec<String>);
 
+// a dug treasure waiting to be cashed, the server only needs its id
+#[derive(Clone, Debug)]
+pub struct Treasure {
+    pub id: String,
+    pub depth: u64,
+    pub pos_x: u64,
+    pub pos_y: u64,
+}
+
 impl TreasureList {
     pub fn new() -> TreasureList {
-        TreasureList { 0: vec![] }
+        TreasureList(vec![])
     }
 }
 
@@ -258,6 +280,29 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_split_quad() {
+        //(size_x,size_y of parent tile,expected_tile_count)
+        let test_data = [(1, 1, 1), (2, 1, 2), (1, 7, 2), (2, 2, 4), (31, 17, 4), (32, 1, 2)];
+
+        for t in test_data.iter() {
+            let parent_tile = Tile::new(3, 5, t.0, t.1);
+            let v = parent_tile.split_quad();
+            assert_eq!(v.len(), t.2, "split of {}", parent_tile);
+            let mut cells = std::collections::HashSet::new();
+            for tile in v.iter() {
+                assert!(tile.area.size_x > 0 && tile.area.size_y > 0);
+                for x in tile.area.pos_x..tile.area.pos_x + tile.area.size_x {
+                    for y in tile.area.pos_y..tile.area.pos_y + tile.area.size_y {
+                        assert!(cells.insert((x, y)), "overlapping tiles in {}", parent_tile);
+                    }
+                }
+            }
+            assert_eq!(cells.len() as u64, t.0 * t.1);
+            assert!(cells.iter().all(|(x, y)| *x >= 3 && *x < 3 + t.0 && *y >= 5 && *y < 5 + t.1));
+        }
+    }
+
     fn check_tiles_in_vec(size_x: u64, preferred_tile_size: u64, expected_tile_count: u64) {
         let parent_tile = Tile::new(0, 0, size_x, 1);
         let v = parent_tile.split_to_tiles(preferred_tile_size);
@@ -269,10 +314,10 @@ mod tests {
         for t in v.iter() {
             print!("{}", *t);
             if pos == t.area.pos_x {
-                assert!(false, "tiles with dublicate pos_x")
+                panic!("tiles with dublicate pos_x")
             }
             if t.area.size_x != preferred_tile_size && t.area.size_x != 1 {
-                assert!(false, "")
+                panic!("")
             }
             pos = t.area.pos_x;
             size_sum += t.area.size_x;
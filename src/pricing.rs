@@ -0,0 +1,229 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+// the tier digs count as this many licenses bought at the price
+const PRIOR_SAMPLES: u64 = 4;
+
+const MAX_TIERS: usize = 8;
+
+// coins paid for a license -> digs it is expected to allow, written as
+// "1:5,6:10,11:20,21:40". prices and digs are both ascending and non-zero
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct LicenseTiers {
+    tiers: [(usize, u64); MAX_TIERS],
+    len: usize,
+}
+
+impl LicenseTiers {
+    pub fn iter(&self) -> impl Iterator<Item = &(usize, u64)> {
+        self.tiers[..self.len].iter()
+    }
+
+    // every tier must cost min_cost..=max_cost coins
+    pub fn validate(&self, min_cost: usize, max_cost: usize) -> Result<(), String> {
+        if min_cost > max_cost {
+            return Err(format!(
+                "license tiers: min cost {} is above max cost {}",
+                min_cost, max_cost
+            ));
+        }
+        match self.iter().find(|(price, _)| *price < min_cost || *price > max_cost) {
+            Some((price, digs)) => Err(format!(
+                "license tiers: {}:{} is outside the {}..{} coins attorneys may pay",
+                price, digs, min_cost, max_cost
+            )),
+            None => Ok(()),
+        }
+    }
+
+    // the tiers attorneys may pay for
+    pub fn between(&self, min_cost: usize, max_cost: usize) -> Vec<(usize, u64)> {
+        self.iter()
+            .copied()
+            .filter(|(price, _)| *price >= min_cost && *price <= max_cost)
+            .collect()
+    }
+}
+
+impl FromStr for LicenseTiers {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tiers = LicenseTiers {
+            tiers: [(0, 0); MAX_TIERS],
+            len: 0,
+        };
+        for part in s.split(',').filter(|p| !p.is_empty()) {
+            let (price, digs) = part
+                .split_once(':')
+                .ok_or(format!("license tiers: no digs in {}", part))?;
+            let price: usize = price
+                .parse()
+                .map_err(|e| format!("license tiers: {} in {}", e, part))?;
+            let digs: u64 = digs
+                .parse()
+                .map_err(|e| format!("license tiers: {} in {}", e, part))?;
+            if price == 0 || digs == 0 {
+                return Err(format!("license tiers: zero in {}", part));
+            }
+            if tiers.len == MAX_TIERS {
+                return Err(format!("license tiers: more than {} tiers", MAX_TIERS));
+            }
+            if tiers.len > 0 && tiers.tiers[tiers.len - 1].0 >= price {
+                return Err(format!("license tiers: prices are not ascending at {}", part));
+            }
+            if tiers.len > 0 && tiers.tiers[tiers.len - 1].1 >= digs {
+                return Err(format!("license tiers: digs are not ascending at {}", part));
+            }
+            tiers.tiers[tiers.len] = (price, digs);
+            tiers.len += 1;
+        }
+        if tiers.len == 0 {
+            return Err("license tiers: no tiers".to_string());
+        }
+        Ok(tiers)
+    }
+}
+
+impl Display for LicenseTiers {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let tiers: Vec<String> = self
+            .iter()
+            .map(|(price, digs)| format!("{}:{}", price, digs))
+            .collect();
+        write!(f, "{}", tiers.join(","))
+    }
+}
+
+// digs allowed per license, learned per price paid from /licenses answers
+// starting from the tier table
+pub struct LicensePricing {
+    // (price, expected digs) attorneys may pay, ascending
+    prices: Vec<(usize, u64)>,
+    // price -> (licenses, digs allowed)
+    samples: BTreeMap<usize, (u64, u64)>,
+}
+
+impl LicensePricing {
+    pub fn new(tiers: LicenseTiers, min_cost: usize, max_cost: usize) -> LicensePricing {
+        LicensePricing {
+            prices: tiers.between(min_cost, max_cost),
+            samples: BTreeMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, price: usize, dig_allowed: u64) {
+        let s = self.samples.entry(price).or_default();
+        s.0 += 1;
+        s.1 += dig_allowed;
+    }
+
+    // digs expected for a price, the tier digs weigh as PRIOR_SAMPLES licenses
+    pub fn estimate(&self, price: usize) -> Option<f32> {
+        let prior = self.prices.iter().find(|(p, _)| *p == price).map(|t| t.1);
+        let (licenses, digs) = self.samples.get(&price).copied().unwrap_or_default();
+        match prior {
+            Some(expected) => Some(
+                (digs + expected * PRIOR_SAMPLES) as f32 / (licenses + PRIOR_SAMPLES) as f32,
+            ),
+            None if licenses > 0 => Some(digs as f32 / licenses as f32),
+            None => None,
+        }
+    }
+
+    // the price to pay out of `available` coins, 0 buys a free license.
+    // an empty dig backlog buys the most digs per coin; as `backlog` (0..1)
+    // fills up, pricier licenses with more digs win as long as their digs
+    // per coin stay within the backlog share of the best
+    pub fn choose(&self, available: usize, backlog: f32) -> usize {
+        let known: Vec<(usize, f32)> = self
+            .prices
+            .iter()
+            .filter(|(price, _)| *price <= available)
+            .filter_map(|(price, _)| self.estimate(*price).map(|digs| (*price, digs)))
+            .collect();
+        let best = known
+            .iter()
+            .map(|(price, digs)| digs / *price as f32)
+            .fold(0.0, f32::max);
+        let floor = best * (1.0 - backlog.clamp(0.0, 1.0));
+        known
+            .iter()
+            .filter(|(price, digs)| digs / *price as f32 >= floor)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map_or(0, |(price, _)| *price)
+    }
+
+    // "price:digs" of the bought prices, e.g. "1:3.0,6:6.2"
+    pub fn summary(&self) -> String {
+        self.samples
+            .keys()
+            .filter_map(|price| {
+                self.estimate(*price)
+                    .map(|digs| format!("{}:{:.1}", price, digs))
+            })
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pricing::{LicensePricing, LicenseTiers};
+
+    #[test]
+    fn test_license_tiers() {
+        let tiers: LicenseTiers = "1:5,6:10,11:20,21:40".parse().unwrap();
+        assert_eq!(tiers.to_string(), "1:5,6:10,11:20,21:40");
+        assert_eq!(tiers.between(2, 11), vec![(6, 10), (11, 20)]);
+        assert!("".parse::<LicenseTiers>().is_err());
+        assert!("1:5,1:6".parse::<LicenseTiers>().is_err());
+        assert!("6:10,1:5".parse::<LicenseTiers>().is_err());
+        assert!("1:5,6:5".parse::<LicenseTiers>().is_err());
+        assert!("1:0".parse::<LicenseTiers>().is_err());
+        assert!("0:3".parse::<LicenseTiers>().is_err());
+        assert!("1:x".parse::<LicenseTiers>().is_err());
+        assert!("1,2,3,4,5,6,7,8,9"
+            .split(',')
+            .map(|p| format!("{}:{}", p, p))
+            .collect::<Vec<_>>()
+            .join(",")
+            .parse::<LicenseTiers>()
+            .is_err());
+
+        assert_eq!(tiers.validate(1, 21), Ok(()));
+        assert!(tiers.validate(1, 11).is_err());
+        assert!(tiers.validate(6, 21).is_err());
+        assert!(tiers.validate(21, 1).is_err());
+    }
+
+    #[test]
+    fn test_license_pricing() {
+        let tiers: LicenseTiers = "1:3,6:12,11:20,21:40".parse().unwrap();
+        let mut pricing = LicensePricing::new(tiers, 1, 11);
+        assert_eq!(pricing.choose(0, 0.0), 0);
+        // 3, 2 and 1.8 digs per coin from the table
+        assert_eq!(pricing.choose(20, 0.0), 1);
+        assert_eq!(pricing.choose(20, 0.35), 6);
+        assert_eq!(pricing.choose(20, 1.0), 11);
+        assert_eq!(pricing.choose(10, 1.0), 6);
+
+        // 11 coins turn out to buy 8 digs, the table weighs as 4 licenses
+        for _ in 0..4 {
+            pricing.record(11, 8);
+        }
+        assert_eq!(pricing.estimate(11), Some(14.0));
+        assert_eq!(pricing.choose(20, 1.0), 11);
+        for _ in 0..8 {
+            pricing.record(11, 8);
+        }
+        assert_eq!(pricing.estimate(11), Some(11.0));
+        assert_eq!(pricing.choose(20, 1.0), 6);
+        pricing.record(0, 3);
+        assert_eq!(pricing.estimate(0), Some(3.0));
+        assert_eq!(pricing.summary(), "0:3.0,11:11.0");
+
+        assert_eq!(LicensePricing::new(tiers, 0, 0).choose(100, 1.0), 0);
+    }
+}
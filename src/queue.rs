@@ -0,0 +1,433 @@
+use crate::model::{Tile, Treasure};
+use crate::value::ValueEstimator;
+use async_std::channel::{bounded, Receiver, Sender};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock};
+
+// which of equally scored tiles is dug first
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TieBreak {
+    Fifo,
+    Lifo,
+}
+
+impl FromStr for TieBreak {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fifo" => Ok(TieBreak::Fifo),
+            "lifo" => Ok(TieBreak::Lifo),
+            _ => Err(format!("unknown tie break: {}", s)),
+        }
+    }
+}
+
+impl Display for TieBreak {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            TieBreak::Fifo => "fifo",
+            TieBreak::Lifo => "lifo",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// which treasure the accountants cash first
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum CashOrder {
+    // deepest first
+    Depth,
+    // oldest first
+    Age,
+    // highest learned value first, depth stands in until it is learned
+    Value,
+}
+
+impl FromStr for CashOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "depth" => Ok(CashOrder::Depth),
+            "age" => Ok(CashOrder::Age),
+            "value" => Ok(CashOrder::Value),
+            _ => Err(format!("unknown cash order: {}", s)),
+        }
+    }
+}
+
+impl Display for CashOrder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            CashOrder::Depth => "depth",
+            CashOrder::Age => "age",
+            CashOrder::Value => "value",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// treasures expected per dig request left to find them; the point is dug
+// from `depth` + 1 until every treasure is found or max depth is reached
+pub fn dig_score(amount: u64, depth: u64, max_depth: u64) -> f64 {
+    if depth >= max_depth {
+        return 0.0;
+    }
+    amount as f64 / (max_depth - depth) as f64
+}
+
+struct Entry {
+    score: f64,
+    // insertion order, flipped for fifo so the greater key always wins
+    order: u64,
+    tile: Tile,
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then(self.order.cmp(&other.order))
+    }
+}
+
+struct Heap {
+    entries: BinaryHeap<Entry>,
+    pushed: u64,
+    // queued tiles by amount
+    amounts: BTreeMap<u64, usize>,
+}
+
+// bounded dig queue handing out the tile with the best expected value per
+// dig first. senders wait while it is full, receivers while it is empty
+pub struct DigQueue {
+    max_depth: u64,
+    tie_break: TieBreak,
+    heap: Mutex<Heap>,
+    slots_sender: Sender<()>,
+    slots_receiver: Receiver<()>,
+    ready_sender: Sender<()>,
+    ready_receiver: Receiver<()>,
+}
+
+impl DigQueue {
+    pub fn new(capacity: usize, max_depth: u64, tie_break: TieBreak) -> DigQueue {
+        let (slots_sender, slots_receiver) = bounded(capacity);
+        let (ready_sender, ready_receiver) = bounded(capacity);
+        DigQueue {
+            max_depth,
+            tie_break,
+            heap: Mutex::new(Heap {
+                entries: BinaryHeap::new(),
+                pushed: 0,
+                amounts: BTreeMap::new(),
+            }),
+            slots_sender,
+            slots_receiver,
+            ready_sender,
+            ready_receiver,
+        }
+    }
+
+    // `depth` - the deepest level already dug at the tile point
+    pub async fn push(&self, tile: Tile, depth: u64) {
+        self.slots_sender.send(()).await.unwrap();
+        {
+            let mut heap = self.heap.lock().unwrap();
+            heap.pushed += 1;
+            let order = match self.tie_break {
+                TieBreak::Fifo => u64::MAX - heap.pushed,
+                TieBreak::Lifo => heap.pushed,
+            };
+            *heap.amounts.entry(tile.amount).or_default() += 1;
+            heap.entries.push(Entry {
+                score: dig_score(tile.amount, depth, self.max_depth),
+                order,
+                tile,
+            });
+        }
+        self.ready_sender.send(()).await.unwrap();
+    }
+
+    pub async fn pop(&self) -> Tile {
+        self.ready_receiver.recv().await.unwrap();
+        let entry = {
+            let mut heap = self.heap.lock().unwrap();
+            let entry = heap.entries.pop().unwrap();
+            let count = heap.amounts.get_mut(&entry.tile.amount).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                heap.amounts.remove(&entry.tile.amount);
+            }
+            entry
+        };
+        self.slots_receiver.recv().await.unwrap();
+        entry.tile
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.lock().unwrap().entries.len()
+    }
+
+    // queued share of the capacity, 0..1
+    pub fn load(&self) -> f32 {
+        match self.slots_sender.capacity() {
+            Some(capacity) if capacity > 0 => self.len() as f32 / capacity as f32,
+            _ => 0.0,
+        }
+    }
+
+    // "amount:tiles" pairs of the queued tiles, e.g. "1:8,2:3,5:1"
+    pub fn distribution(&self) -> String {
+        self.heap
+            .lock()
+            .unwrap()
+            .amounts
+            .iter()
+            .map(|(amount, count)| format!("{}:{}", amount, count))
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+}
+
+struct TreasureEntry {
+    score: f64,
+    // flipped insertion order, older treasures win ties
+    order: u64,
+    treasure: Treasure,
+}
+
+impl PartialEq for TreasureEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for TreasureEntry {}
+
+impl PartialOrd for TreasureEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TreasureEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then(self.order.cmp(&other.order))
+    }
+}
+
+struct TreasureHeap {
+    entries: BinaryHeap<TreasureEntry>,
+    pushed: u64,
+    // queued treasures by depth
+    depths: BTreeMap<u64, usize>,
+}
+
+// bounded queue of dug treasures waiting to be cashed, ordered by `CashOrder`
+pub struct TreasureQueue {
+    capacity: usize,
+    order: CashOrder,
+    values: Arc<RwLock<ValueEstimator>>,
+    heap: Mutex<TreasureHeap>,
+    slots_sender: Sender<()>,
+    slots_receiver: Receiver<()>,
+    ready_sender: Sender<()>,
+    ready_receiver: Receiver<()>,
+}
+
+impl TreasureQueue {
+    pub fn new(
+        capacity: usize,
+        order: CashOrder,
+        values: Arc<RwLock<ValueEstimator>>,
+    ) -> TreasureQueue {
+        let (slots_sender, slots_receiver) = bounded(capacity);
+        let (ready_sender, ready_receiver) = bounded(capacity);
+        TreasureQueue {
+            capacity,
+            order,
+            values,
+            heap: Mutex::new(TreasureHeap {
+                entries: BinaryHeap::new(),
+                pushed: 0,
+                depths: BTreeMap::new(),
+            }),
+            slots_sender,
+            slots_receiver,
+            ready_sender,
+            ready_receiver,
+        }
+    }
+
+    pub async fn push(&self, treasure: Treasure) {
+        self.slots_sender.send(()).await.unwrap();
+        let score = match self.order {
+            CashOrder::Depth => treasure.depth as f64,
+            CashOrder::Age => 0.0,
+            CashOrder::Value => match self.values.read().unwrap().estimate(treasure.depth) {
+                Some(value) => value as f64,
+                None => treasure.depth as f64,
+            },
+        };
+        {
+            let mut heap = self.heap.lock().unwrap();
+            heap.pushed += 1;
+            let order = u64::MAX - heap.pushed;
+            *heap.depths.entry(treasure.depth).or_default() += 1;
+            heap.entries.push(TreasureEntry {
+                score,
+                order,
+                treasure,
+            });
+        }
+        self.ready_sender.send(()).await.unwrap();
+    }
+
+    pub async fn pop(&self) -> Treasure {
+        self.ready_receiver.recv().await.unwrap();
+        let entry = {
+            let mut heap = self.heap.lock().unwrap();
+            let entry = heap.entries.pop().unwrap();
+            let count = heap.depths.get_mut(&entry.treasure.depth).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                heap.depths.remove(&entry.treasure.depth);
+            }
+            entry
+        };
+        self.slots_receiver.recv().await.unwrap();
+        entry.treasure
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.lock().unwrap().entries.len()
+    }
+
+    // queued share of the capacity, 0..1
+    pub fn load(&self) -> f32 {
+        if self.capacity == 0 {
+            return 0.0;
+        }
+        self.len() as f32 / self.capacity as f32
+    }
+
+    // coins the queued treasures are expected to bring, by the learned
+    // value of their depth or the mean value while it is not learned yet
+    pub fn uncashed_value(&self) -> f32 {
+        let values = self.values.read().unwrap();
+        let mean = values.mean().unwrap_or(0.0);
+        self.heap
+            .lock()
+            .unwrap()
+            .depths
+            .iter()
+            .map(|(depth, count)| values.estimate(*depth).unwrap_or(mean) * *count as f32)
+            .fold(0.0, |sum, value| sum + value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{Tile, Treasure};
+    use crate::queue::{CashOrder, DigQueue, TieBreak, TreasureQueue};
+    use crate::value::ValueEstimator;
+    use std::sync::{Arc, RwLock};
+
+    fn point(x: u64, amount: u64) -> Tile {
+        Tile {
+            amount,
+            ..Tile::new(x, 0, 1, 1)
+        }
+    }
+
+    async fn drain(queue: &DigQueue, n: usize) -> Vec<u64> {
+        let mut result = vec![];
+        for _ in 0..n {
+            result.push(queue.pop().await.area.pos_x);
+        }
+        result
+    }
+
+    #[async_std::test]
+    async fn test_dig_queue() {
+        let queue = DigQueue::new(8, 10, TieBreak::Fifo);
+        queue.push(point(0, 1), 0).await;
+        queue.push(point(1, 2), 0).await;
+        queue.push(point(2, 1), 0).await;
+        // one treasure left in 2 levels beats two in 10
+        queue.push(point(3, 1), 8).await;
+        queue.push(point(4, 1), 10).await;
+        assert_eq!(queue.len(), 5);
+        assert_eq!(queue.load(), 0.625);
+        assert_eq!(queue.distribution(), "1:4,2:1");
+        assert_eq!(drain(&queue, 5).await, vec![3, 1, 0, 2, 4]);
+        assert_eq!(queue.distribution(), "");
+
+        let queue = DigQueue::new(8, 10, TieBreak::Lifo);
+        for x in 0..3 {
+            queue.push(point(x, 1), 0).await;
+        }
+        assert_eq!(drain(&queue, 3).await, vec![2, 1, 0]);
+    }
+
+    fn treasure(id: &str, depth: u64) -> Treasure {
+        Treasure {
+            id: id.to_string(),
+            depth,
+            pos_x: 0,
+            pos_y: 0,
+        }
+    }
+
+    async fn cash_order(queue: &TreasureQueue) -> Vec<String> {
+        let mut result = vec![];
+        while queue.len() > 0 {
+            result.push(queue.pop().await.id);
+        }
+        result
+    }
+
+    #[async_std::test]
+    async fn test_treasure_queue() {
+        let values = Arc::new(RwLock::new(ValueEstimator::new(10)));
+        for (depth, coins) in [(1, 4), (2, 1), (3, 2)] {
+            for _ in 0..8 {
+                values.write().unwrap().record(depth, coins);
+            }
+        }
+        for (order, expected) in [
+            (CashOrder::Depth, ["c", "d", "b", "a"]),
+            (CashOrder::Age, ["a", "b", "c", "d"]),
+            (CashOrder::Value, ["a", "c", "d", "b"]),
+        ] {
+            let queue = TreasureQueue::new(4, order, values.clone());
+            for (id, depth) in [("a", 1), ("b", 2), ("c", 3), ("d", 3)] {
+                queue.push(treasure(id, depth)).await;
+            }
+            assert_eq!(queue.load(), 1.0);
+            assert_eq!(queue.uncashed_value(), 9.0);
+            assert_eq!(cash_order(&queue).await, expected);
+        }
+    }
+}
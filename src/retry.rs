@@ -0,0 +1,175 @@
+use crate::http::{ApiError, ERROR_KINDS};
+use rand::Rng;
+use std::fmt::{Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::{LineWriter, Write};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+use surf::http::convert::Serialize;
+
+// how an endpoint retries failed requests, written as
+// "attempts=5,backoff=10..500,on=transport|timeout|server_error".
+// attempts=0 retries without limit, the backoff doubles from the first
+// value up to the second one and a random half of it is taken off
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    // bit per ERROR_KINDS entry
+    retry_on: u16,
+}
+
+impl RetryPolicy {
+    pub fn is_retryable(&self, e: &ApiError) -> bool {
+        self.retry_on & (1 << e.kind()) != 0
+    }
+
+    // `attempts` - requests sent so far
+    pub fn is_exhausted(&self, attempts: u32) -> bool {
+        self.max_attempts > 0 && attempts >= self.max_attempts
+    }
+
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let delay = self
+            .backoff_ms
+            .saturating_mul(1 << attempts.saturating_sub(1).min(16))
+            .min(self.max_backoff_ms);
+        let jitter = rand::thread_rng().gen_range(0..=delay / 2);
+        Duration::from_millis(delay - jitter)
+    }
+}
+
+impl FromStr for RetryPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut policy = RetryPolicy {
+            max_attempts: 1,
+            backoff_ms: 0,
+            max_backoff_ms: 0,
+            retry_on: 0,
+        };
+        for part in s.split(',').filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or(format!("retry policy: no value in {}", part))?;
+            let number = |v: &str| {
+                v.parse::<u64>()
+                    .map_err(|e| format!("retry policy: {} in {}", e, part))
+            };
+            match key {
+                "attempts" => policy.max_attempts = number(value)? as u32,
+                "backoff" => {
+                    let (from, to) = value
+                        .split_once("..")
+                        .ok_or(format!("retry policy: backoff is not a range in {}", part))?;
+                    policy.backoff_ms = number(from)?;
+                    policy.max_backoff_ms = number(to)?;
+                }
+                "on" => {
+                    for kind in value.split('|') {
+                        let i = ERROR_KINDS
+                            .iter()
+                            .position(|k| *k == kind)
+                            .ok_or(format!("retry policy: unknown error {}", kind))?;
+                        policy.retry_on |= 1 << i;
+                    }
+                }
+                _ => return Err(format!("retry policy: unknown key {}", key)),
+            }
+        }
+        if policy.backoff_ms > policy.max_backoff_ms {
+            return Err(format!("retry policy: backoff range is reversed in {}", s));
+        }
+        Ok(policy)
+    }
+}
+
+impl Display for RetryPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let on: Vec<&str> = ERROR_KINDS
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| self.retry_on & (1 << i) != 0)
+            .map(|(_, kind)| *kind)
+            .collect();
+        write!(
+            f,
+            "attempts={},backoff={}..{},on={}",
+            self.max_attempts,
+            self.backoff_ms,
+            self.max_backoff_ms,
+            on.join("|")
+        )
+    }
+}
+
+#[derive(Serialize)]
+struct DeadLetter<'a> {
+    endpoint: &'a str,
+    payload: serde_json::Value,
+    attempts: u32,
+    error: String,
+}
+
+// requests that ran out of retries, one json line each
+pub struct DeadLetters {
+    writer: Mutex<LineWriter<File>>,
+}
+
+impl DeadLetters {
+    pub fn create(path: &str) -> Result<DeadLetters, std::io::Error> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(DeadLetters {
+            writer: Mutex::new(LineWriter::new(file)),
+        })
+    }
+
+    pub fn write(&self, endpoint: &str, payload: impl Serialize, attempts: u32, e: &ApiError) {
+        let letter = DeadLetter {
+            endpoint,
+            payload: serde_json::to_value(payload).unwrap(),
+            attempts,
+            error: e.to_string(),
+        };
+        let line = serde_json::to_string(&letter).unwrap();
+        if let Err(e) = writeln!(self.writer.lock().unwrap(), "{}", line) {
+            println!("dead letter error: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::http::ApiError;
+    use crate::retry::RetryPolicy;
+    use std::time::Duration;
+
+    #[test]
+    fn test_retry_policy() {
+        let spec = "attempts=3,backoff=10..40,on=timeout|server_error";
+        let policy: RetryPolicy = spec.parse().unwrap();
+        assert_eq!(policy.to_string(), spec);
+        assert!(policy.is_retryable(&ApiError::Timeout));
+        assert!(policy.is_retryable(&ApiError::ServerError(503, "".to_string())));
+        assert!(!policy.is_retryable(&ApiError::NoTreasure));
+        assert!(!policy.is_exhausted(2));
+        assert!(policy.is_exhausted(3));
+
+        for (attempts, max) in [(1, 10), (2, 20), (3, 40), (10, 40)] {
+            let backoff = policy.backoff(attempts);
+            assert!(backoff >= Duration::from_millis(max / 2));
+            assert!(backoff <= Duration::from_millis(max));
+        }
+
+        let unlimited: RetryPolicy = "attempts=0,backoff=0..0,on=transport".parse().unwrap();
+        assert!(!unlimited.is_exhausted(1000));
+        assert_eq!(unlimited.backoff(5), Duration::from_millis(0));
+
+        assert!("attempts=x".parse::<RetryPolicy>().is_err());
+        assert!("backoff=50..10".parse::<RetryPolicy>().is_err());
+        assert!("on=teapot".parse::<RetryPolicy>().is_err());
+    }
+}
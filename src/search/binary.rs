@@ -0,0 +1,66 @@
+use crate::model::Tile;
+use crate::search::{probe, row_seeds, scan, Found, Prober, SearchStrategy};
+use async_trait::async_trait;
+
+// halves a row until it is narrower than flat_threshold, then scans it by flat_size
+pub struct BinarySearch {
+    min_amount: u64,
+    array_size: u64,
+    flat_threshold: u64,
+    flat_size: u64,
+}
+
+impl BinarySearch {
+    pub fn new(
+        min_amount: u64,
+        array_size: u64,
+        flat_threshold: u64,
+        flat_size: u64,
+    ) -> BinarySearch {
+        BinarySearch {
+            min_amount,
+            array_size,
+            flat_threshold,
+            flat_size,
+        }
+    }
+}
+
+#[async_trait]
+impl SearchStrategy for BinarySearch {
+    fn seeds(&self, world_size: u64) -> Box<dyn Iterator<Item = Tile> + Send> {
+        row_seeds(world_size, self.array_size)
+    }
+
+    async fn search(&self, tile: Tile, top: bool, prober: &dyn Prober) -> Found {
+        let mut tile = match probe(tile, prober).await {
+            Some(tile) => tile,
+            None => return Found::failed(),
+        };
+        let min_amount = if top { self.min_amount } else { 1 };
+        if !tile.has_treasures(min_amount) {
+            return Found::default();
+        }
+        if tile.is_single_point() {
+            return Found::new(vec![tile]);
+        }
+        if tile.area.size_x > self.flat_threshold {
+            let (left, mut right) = tile.split();
+            let mut result = self.search(left, false, prober).await;
+            tile.amount -= result.amount();
+            if tile.amount > 0 && !result.failed {
+                right.amount = tile.amount;
+                result.append(self.search(right, false, prober).await);
+            }
+            result
+        } else {
+            let tile_size = if tile.area.size_x <= self.flat_size {
+                1
+            } else {
+                self.flat_size
+            };
+            let children = tile.split_to_tiles(tile_size);
+            scan(self, tile, children, false, prober).await
+        }
+    }
+}
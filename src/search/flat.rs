@@ -0,0 +1,57 @@
+use crate::model::Tile;
+use crate::search::{probe, row_seeds, scan, Found, Prober, SearchStrategy};
+use async_trait::async_trait;
+
+// scans a row by flat_threshold wide tiles, those by flat_size, then by points
+pub struct FlatSearch {
+    min_amount: u64,
+    array_size: u64,
+    flat_threshold: u64,
+    flat_size: u64,
+}
+
+impl FlatSearch {
+    pub fn new(
+        min_amount: u64,
+        array_size: u64,
+        flat_threshold: u64,
+        flat_size: u64,
+    ) -> FlatSearch {
+        FlatSearch {
+            min_amount,
+            array_size,
+            flat_threshold,
+            flat_size,
+        }
+    }
+}
+
+#[async_trait]
+impl SearchStrategy for FlatSearch {
+    fn seeds(&self, world_size: u64) -> Box<dyn Iterator<Item = Tile> + Send> {
+        row_seeds(world_size, self.array_size)
+    }
+
+    async fn search(&self, tile: Tile, top: bool, prober: &dyn Prober) -> Found {
+        let tile = match probe(tile, prober).await {
+            Some(tile) => tile,
+            None => return Found::failed(),
+        };
+        let min_amount = if top { self.min_amount } else { 1 };
+        if !tile.has_treasures(min_amount) {
+            return Found::default();
+        }
+        if tile.is_single_point() {
+            return Found::new(vec![tile]);
+        }
+        let tile_size = if tile.area.size_x <= self.flat_size {
+            1
+        } else if tile.area.size_x > self.flat_threshold {
+            self.flat_threshold
+        } else {
+            self.flat_size
+        };
+        let children = tile.split_to_tiles(tile_size);
+        scan(self, tile, children, false, prober).await
+    }
+}
@@ -0,0 +1,265 @@
+pub mod binary;
+pub mod flat;
+pub mod planned;
+pub mod quadtree;
+
+use crate::config::Config;
+use crate::model::Tile;
+use crate::search::binary::BinarySearch;
+use crate::search::flat::FlatSearch;
+use crate::search::planned::PlannedSearch;
+use crate::search::quadtree::QuadtreeSearch;
+use async_recursion::async_recursion;
+use async_trait::async_trait;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+// issues explore requests on behalf of a strategy
+#[async_trait]
+pub trait Prober: Sync {
+    // returns the tile with its amount, None if the explore failed or the game is over
+    async fn explore(&self, tile: Tile) -> Option<Tile>;
+    // reports a point whose amount was inferred instead of explored
+    async fn report_calculated(&self, tile: &Tile);
+    // reports planned and paid explore price of a seed, or a seed skipped as too expensive
+    async fn report_plan(&self, _expected: f32, _actual: f32, _skipped: bool) {}
+}
+
+#[async_trait]
+pub trait SearchStrategy: Send + Sync {
+    // seed areas covering the world, in the order they should be explored
+    fn seeds(&self, world_size: u64) -> Box<dyn Iterator<Item = Tile> + Send>;
+    // returns the point tiles with treasures inside tile, `top` marks a seed area
+    async fn search(&self, tile: Tile, top: bool, prober: &dyn Prober) -> Found;
+}
+
+// point tiles with treasures found by a search. a failed explore drops the
+// rest of the searched tile, the tiles found before it are kept
+#[derive(Default)]
+pub struct Found {
+    pub tiles: Vec<Tile>,
+    pub failed: bool,
+}
+
+impl Found {
+    pub fn new(tiles: Vec<Tile>) -> Found {
+        Found {
+            tiles,
+            failed: false,
+        }
+    }
+
+    pub fn failed() -> Found {
+        Found {
+            tiles: Vec::new(),
+            failed: true,
+        }
+    }
+
+    pub fn append(&mut self, mut other: Found) {
+        self.tiles.append(&mut other.tiles);
+        self.failed |= other.failed;
+    }
+
+    pub fn amount(&self) -> u64 {
+        self.tiles.iter().map(|t| t.amount).sum()
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SearchStrategyKind {
+    Binary,
+    Flat,
+    Quadtree,
+    Planned,
+}
+
+impl FromStr for SearchStrategyKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "binary" => Ok(SearchStrategyKind::Binary),
+            "flat" => Ok(SearchStrategyKind::Flat),
+            "quadtree" => Ok(SearchStrategyKind::Quadtree),
+            "planned" => Ok(SearchStrategyKind::Planned),
+            _ => Err(format!("unknown search strategy: {}", s)),
+        }
+    }
+}
+
+impl Display for SearchStrategyKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            SearchStrategyKind::Binary => "binary",
+            SearchStrategyKind::Flat => "flat",
+            SearchStrategyKind::Quadtree => "quadtree",
+            SearchStrategyKind::Planned => "planned",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+pub fn new_strategy(c: &Config) -> Box<dyn SearchStrategy> {
+    match c.search_strategy {
+        SearchStrategyKind::Binary => Box::new(BinarySearch::new(
+            c.search_min_amount,
+            c.search_initial_array_size,
+            c.search_to_flat_threshold,
+            c.search_flat_size,
+        )),
+        SearchStrategyKind::Flat => Box::new(FlatSearch::new(
+            c.search_min_amount,
+            c.search_initial_array_size,
+            c.search_to_flat_threshold,
+            c.search_flat_size,
+        )),
+        SearchStrategyKind::Quadtree => Box::new(QuadtreeSearch::new(
+            c.search_min_amount,
+            c.search_block_size_x,
+            c.search_block_size_y,
+        )),
+        SearchStrategyKind::Planned => Box::new(PlannedSearch::new(
+            c.search_min_amount,
+            c.search_initial_array_size,
+            c.search_planner_max_split,
+        )),
+    }
+}
+
+// 1-row strips of array_size, the row tail that doesn't fit is skipped
+pub fn row_seeds(world_size: u64, array_size: u64) -> Box<dyn Iterator<Item = Tile> + Send> {
+    Box::new((0..world_size).flat_map(move |y| {
+        (0..world_size - array_size)
+            .step_by(array_size as usize)
+            .map(move |x| Tile::new(x, y, array_size, 1))
+    }))
+}
+
+// explores tile unless its amount is already known
+pub async fn probe(tile: Tile, prober: &dyn Prober) -> Option<Tile> {
+    if tile.amount == 0 {
+        prober.explore(tile).await
+    } else {
+        Some(tile)
+    }
+}
+
+// searches children of an explored tile one by one, the last child gets
+// whatever amount is left without being explored. it is handed on as it is
+// unless `search_last` is set and it is wider than a point
+#[async_recursion]
+pub async fn scan(
+    strategy: &dyn SearchStrategy,
+    mut tile: Tile,
+    children: Vec<Tile>,
+    search_last: bool,
+    prober: &dyn Prober,
+) -> Found {
+    let mut result = Found::default();
+    let len = children.len();
+
+    for (cur, mut t) in children.into_iter().enumerate() {
+        if cur + 1 == len {
+            t.amount = tile.amount;
+            if t.is_single_point() {
+                prober.report_calculated(&t).await;
+            }
+            if t.is_single_point() || !search_last {
+                result.tiles.push(t);
+                break;
+            }
+        }
+        let found = strategy.search(t, false, prober).await;
+        tile.amount -= found.amount();
+        result.append(found);
+        if tile.amount == 0 || result.failed {
+            break;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock::world::World;
+    use crate::model::Tile;
+    use crate::search::binary::BinarySearch;
+    use crate::search::flat::FlatSearch;
+    use crate::search::planned::PlannedSearch;
+    use crate::search::quadtree::QuadtreeSearch;
+    use crate::search::{Prober, SearchStrategy};
+    use async_trait::async_trait;
+    use std::collections::HashSet;
+    use std::sync::Mutex;
+
+    struct WorldProber {
+        world: World,
+        explores: Mutex<u64>,
+        // explores after this many fail
+        fail_after: Mutex<u64>,
+    }
+
+    #[async_trait]
+    impl Prober for WorldProber {
+        async fn explore(&self, tile: Tile) -> Option<Tile> {
+            let mut explores = self.explores.lock().unwrap();
+            if *explores >= *self.fail_after.lock().unwrap() {
+                return None;
+            }
+            *explores += 1;
+            self.world.explore(tile.area).ok()
+        }
+        async fn report_calculated(&self, _tile: &Tile) {}
+    }
+
+    // `points` strategies search every tile down to single points
+    async fn check_strategy(strategy: &dyn SearchStrategy, points: bool) {
+        let size = 64;
+        let prober = WorldProber {
+            world: World::new(size, 10, 150, 10, 11),
+            explores: Mutex::new(0),
+            fail_after: Mutex::new(u64::MAX),
+        };
+        let mut found = HashSet::new();
+        let mut covered = 0;
+        for seed in strategy.seeds(size) {
+            covered += seed.area.size_x * seed.area.size_y;
+            let seed_amount = prober.world.explore(seed.area.clone()).unwrap().amount;
+            let result = strategy.search(seed, true, &prober).await;
+            assert!(!result.failed);
+            assert_eq!(result.amount(), seed_amount);
+            for t in result.tiles {
+                assert!(t.is_single_point() || !points);
+                assert_eq!(prober.world.explore(t.area.clone()).unwrap().amount, t.amount);
+                assert!(found.insert((t.area.pos_x, t.area.pos_y)));
+            }
+        }
+        assert!(covered > 0 && covered <= size * size);
+        assert!(*prober.explores.lock().unwrap() > 0);
+
+        // only the seed is explored, the search stops at the first failed explore
+        let mut failed = 0;
+        for seed in strategy.seeds(size) {
+            let seed_amount = prober.world.explore(seed.area.clone()).unwrap().amount;
+            let explores = *prober.explores.lock().unwrap();
+            *prober.fail_after.lock().unwrap() = explores + 1;
+            let result = strategy.search(seed, true, &prober).await;
+            assert!(result.amount() <= seed_amount);
+            if result.failed {
+                failed += 1;
+                assert_eq!(*prober.explores.lock().unwrap(), explores + 1);
+            }
+        }
+        assert!(failed > 0);
+    }
+
+    #[async_std::test]
+    async fn test_strategies() {
+        check_strategy(&BinarySearch::new(1, 31, 8, 3), false).await;
+        check_strategy(&FlatSearch::new(1, 31, 8, 3), false).await;
+        check_strategy(&FlatSearch::new(1, 30, 6, 3), false).await;
+        check_strategy(&QuadtreeSearch::new(1, 16, 8), true).await;
+        check_strategy(&PlannedSearch::new(1, 31, 8), true).await;
+    }
+}
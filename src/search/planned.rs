@@ -0,0 +1,233 @@
+use crate::context::explore_price;
+use crate::model::Tile;
+use crate::search::{probe, row_seeds, scan, Found, Prober, SearchStrategy};
+use async_trait::async_trait;
+use std::cmp::min;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+// amounts above are planned as if the tile held this many treasures
+const MAX_PLANNED_AMOUNT: u64 = 32;
+// seeds explored before the observed density is trusted for skipping
+const MIN_DENSITY_SEEDS: u64 = 16;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Plan {
+    pub children: u64,
+    // expected explore price to locate every treasure of the tile
+    pub cost: f32,
+}
+
+// picks how many even children a row tile is cut into, using the explore
+// price table and assuming the known amount is spread uniformly.
+// child i is explored only if the first i children didn't hold everything,
+// the last child is never explored
+pub struct SplitPlanner {
+    max_split: u64,
+    plans: Mutex<HashMap<(u64, u64, u64), Plan>>,
+}
+
+impl SplitPlanner {
+    pub fn new(max_split: u64) -> SplitPlanner {
+        SplitPlanner {
+            max_split: max_split.max(2),
+            plans: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn plan(&self, size_x: u64, size_y: u64, amount: u64) -> Plan {
+        let amount = amount.clamp(1, MAX_PLANNED_AMOUNT);
+        if size_x <= 1 {
+            return Plan {
+                children: 1,
+                cost: 0.0,
+            };
+        }
+        if let Some(plan) = self.plans.lock().unwrap().get(&(size_x, size_y, amount)) {
+            return *plan;
+        }
+
+        let mut best = Plan {
+            children: 0,
+            cost: f32::MAX,
+        };
+        for k in 2..=min(size_x, self.max_split) {
+            let child_x = size_x.div_ceil(k);
+            let mut cost = 0.0;
+            for i in 0..k - 1 {
+                let explored = 1.0 - (i as f32 / k as f32).powi(amount as i32);
+                cost += explored * explore_price(child_x * size_y);
+            }
+            let nonempty = k as f32 * (1.0 - (1.0 - 1.0 / k as f32).powi(amount as i32));
+            let child_amount = (amount as f32 / nonempty).round().max(1.0) as u64;
+            cost += nonempty * self.plan(child_x, size_y, child_amount).cost;
+            if cost < best.cost {
+                best = Plan { children: k, cost };
+            }
+        }
+        self.plans
+            .lock()
+            .unwrap()
+            .insert((size_x, size_y, amount), best);
+        best
+    }
+
+    // expected price per treasure of exploring and searching a fresh seed
+    pub fn seed_cost_per_treasure(&self, size_x: u64, size_y: u64, density: f32) -> f32 {
+        let expected = density * (size_x * size_y) as f32;
+        if expected <= 0.0 {
+            return f32::MAX;
+        }
+        let mut cost = explore_price(size_x * size_y);
+        let mut probability = (-expected).exp();
+        for a in 1..=MAX_PLANNED_AMOUNT {
+            probability *= expected / a as f32;
+            cost += probability * self.plan(size_x, size_y, a).cost;
+        }
+        cost / expected
+    }
+}
+
+// counts what a single seed search actually paid for explores
+struct CostProber<'a> {
+    inner: &'a dyn Prober,
+    spent: Mutex<f32>,
+}
+
+#[async_trait]
+impl<'a> Prober for CostProber<'a> {
+    async fn explore(&self, tile: Tile) -> Option<Tile> {
+        *self.spent.lock().unwrap() += explore_price(tile.area.size_x * tile.area.size_y);
+        self.inner.explore(tile).await
+    }
+
+    async fn report_calculated(&self, tile: &Tile) {
+        self.inner.report_calculated(tile).await
+    }
+}
+
+// splits rows into the number of children the planner finds cheapest and skips
+// seeds that cost more per treasure than an average fresh seed
+pub struct PlannedSearch {
+    min_amount: u64,
+    array_size: u64,
+    planner: SplitPlanner,
+    // explored seeds, their cells and treasures found in them
+    density: Mutex<(u64, u64, u64)>,
+}
+
+impl PlannedSearch {
+    pub fn new(min_amount: u64, array_size: u64, max_split: u64) -> PlannedSearch {
+        PlannedSearch {
+            min_amount,
+            array_size,
+            planner: SplitPlanner::new(max_split),
+            density: Mutex::new((0, 0, 0)),
+        }
+    }
+
+    fn observe(&self, tile: &Tile) -> Option<f32> {
+        let mut density = self.density.lock().unwrap();
+        density.0 += 1;
+        density.1 += tile.area.size_x * tile.area.size_y;
+        density.2 += tile.amount;
+        if density.0 < MIN_DENSITY_SEEDS {
+            None
+        } else {
+            Some(density.2 as f32 / density.1 as f32)
+        }
+    }
+
+    async fn search_seed(&self, tile: Tile, prober: &dyn Prober) -> Found {
+        let counting = CostProber {
+            inner: prober,
+            spent: Mutex::new(0.0),
+        };
+        let explored = tile.amount == 0;
+        let tile = match probe(tile, &counting).await {
+            Some(tile) => tile,
+            None => return Found::failed(),
+        };
+        let density = if explored { self.observe(&tile) } else { None };
+        if !tile.has_treasures(self.min_amount) {
+            return Found::default();
+        }
+
+        let plan = self
+            .planner
+            .plan(tile.area.size_x, tile.area.size_y, tile.amount);
+        if let Some(density) = density {
+            let fresh =
+                self.planner
+                    .seed_cost_per_treasure(tile.area.size_x, tile.area.size_y, density);
+            if plan.cost / tile.amount as f32 > fresh {
+                prober.report_plan(0.0, 0.0, true).await;
+                return Found::default();
+            }
+        }
+
+        let expected = *counting.spent.lock().unwrap() + plan.cost;
+        let result = self.search(tile, false, &counting).await;
+        let actual = *counting.spent.lock().unwrap();
+        prober.report_plan(expected, actual, false).await;
+        result
+    }
+}
+
+#[async_trait]
+impl SearchStrategy for PlannedSearch {
+    fn seeds(&self, world_size: u64) -> Box<dyn Iterator<Item = Tile> + Send> {
+        row_seeds(world_size, self.array_size)
+    }
+
+    async fn search(&self, tile: Tile, top: bool, prober: &dyn Prober) -> Found {
+        if top {
+            return self.search_seed(tile, prober).await;
+        }
+        let tile = match probe(tile, prober).await {
+            Some(tile) => tile,
+            None => return Found::failed(),
+        };
+        if !tile.has_treasures(1) {
+            return Found::default();
+        }
+        if tile.is_single_point() {
+            return Found::new(vec![tile]);
+        }
+        let plan = self
+            .planner
+            .plan(tile.area.size_x, tile.area.size_y, tile.amount);
+        let children = tile.split_even(plan.children);
+        scan(self, tile, children, true, prober).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::context::explore_price;
+    use crate::search::planned::SplitPlanner;
+
+    #[test]
+    fn test_split_planner() {
+        let planner = SplitPlanner::new(8);
+        assert_eq!(planner.plan(1, 1, 3).cost, 0.0);
+        let two = planner.plan(2, 1, 1);
+        assert_eq!(two.children, 2);
+        assert_eq!(two.cost, explore_price(1));
+
+        // splitting never plans more than exploring every point of the row
+        for size in 2..64 {
+            for amount in 1..5 {
+                let plan = planner.plan(size, 1, amount);
+                assert!(plan.children >= 2 && plan.children <= 8);
+                assert!(plan.cost <= (size - 1) as f32 * explore_price(1));
+            }
+        }
+
+        assert!(
+            planner.seed_cost_per_treasure(31, 1, 0.2)
+                < planner.seed_cost_per_treasure(31, 1, 0.01)
+        );
+        assert_eq!(planner.seed_cost_per_treasure(31, 1, 0.0), f32::MAX);
+    }
+}
@@ -0,0 +1,75 @@
+use crate::model::Tile;
+use crate::search::{probe, Found, Prober, SearchStrategy};
+use async_trait::async_trait;
+use std::cmp::min;
+
+// explores rectangular blocks and splits them along both axes until single points
+pub struct QuadtreeSearch {
+    min_amount: u64,
+    block_size_x: u64,
+    block_size_y: u64,
+}
+
+impl QuadtreeSearch {
+    pub fn new(min_amount: u64, block_size_x: u64, block_size_y: u64) -> QuadtreeSearch {
+        QuadtreeSearch {
+            min_amount,
+            block_size_x,
+            block_size_y,
+        }
+    }
+}
+
+#[async_trait]
+impl SearchStrategy for QuadtreeSearch {
+    fn seeds(&self, world_size: u64) -> Box<dyn Iterator<Item = Tile> + Send> {
+        let (block_x, block_y) = (self.block_size_x, self.block_size_y);
+        Box::new(
+            (0..world_size)
+                .step_by(block_y as usize)
+                .flat_map(move |y| {
+                    (0..world_size).step_by(block_x as usize).map(move |x| {
+                        Tile::new(
+                            x,
+                            y,
+                            min(block_x, world_size - x),
+                            min(block_y, world_size - y),
+                        )
+                    })
+                }),
+        )
+    }
+
+    async fn search(&self, tile: Tile, top: bool, prober: &dyn Prober) -> Found {
+        let mut tile = match probe(tile, prober).await {
+            Some(tile) => tile,
+            None => return Found::failed(),
+        };
+        let min_amount = if top { self.min_amount } else { 1 };
+        if !tile.has_treasures(min_amount) {
+            return Found::default();
+        }
+        if tile.is_single_point() {
+            return Found::new(vec![tile]);
+        }
+
+        let mut result = Found::default();
+        let children = tile.split_quad();
+        let len = children.len();
+        for (cur, mut t) in children.into_iter().enumerate() {
+            if cur + 1 == len {
+                t.amount = tile.amount;
+                if t.is_single_point() {
+                    prober.report_calculated(&t).await;
+                }
+            }
+            let found = self.search(t, false, prober).await;
+            tile.amount -= found.amount();
+            result.append(found);
+            if tile.amount == 0 || result.failed {
+                break;
+            }
+        }
+        result
+    }
+}
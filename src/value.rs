@@ -0,0 +1,93 @@
+// a depth is trusted after this many cashed treasures
+const MIN_SAMPLES: u64 = 8;
+
+// coins paid per cashed treasure, learned per depth from /cash answers
+pub struct ValueEstimator {
+    // (treasures, coins) by depth - 1
+    depths: Vec<(u64, u64)>,
+}
+
+impl ValueEstimator {
+    pub fn new(max_depth: u64) -> ValueEstimator {
+        ValueEstimator {
+            depths: vec![(0, 0); max_depth as usize],
+        }
+    }
+
+    pub fn record(&mut self, depth: u64, coins: u64) {
+        if let Some(d) = self.depths.get_mut(depth.wrapping_sub(1) as usize) {
+            d.0 += 1;
+            d.1 += coins;
+        }
+    }
+
+    pub fn estimate(&self, depth: u64) -> Option<f32> {
+        match self.depths.get(depth.wrapping_sub(1) as usize) {
+            Some((treasures, coins)) if *treasures >= MIN_SAMPLES => {
+                Some(*coins as f32 / *treasures as f32)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn mean(&self) -> Option<f32> {
+        let (treasures, coins) = self
+            .depths
+            .iter()
+            .fold((0, 0), |acc, d| (acc.0 + d.0, acc.1 + d.1));
+        if treasures < MIN_SAMPLES {
+            None
+        } else {
+            Some(coins as f32 / treasures as f32)
+        }
+    }
+
+    // a cash request costs the coins an average treasure would have brought in
+    // its place, scaled by how busy accountants are (`load` from 0 to 1).
+    // unknown depths are cashed to learn their value
+    pub fn is_worth(&self, depth: u64, load: f32) -> bool {
+        match (self.estimate(depth), self.mean()) {
+            (Some(value), Some(mean)) => value >= mean * load,
+            _ => true,
+        }
+    }
+
+    // "depth:coins" of the learned depths, e.g. "1:1.5,2:3.1"
+    pub fn summary(&self) -> String {
+        (1..=self.depths.len() as u64)
+            .filter_map(|depth| {
+                self.estimate(depth)
+                    .map(|value| format!("{}:{:.1}", depth, value))
+            })
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::value::ValueEstimator;
+
+    #[test]
+    fn test_value_estimator() {
+        let mut values = ValueEstimator::new(10);
+        assert_eq!(values.estimate(1), None);
+        assert!(values.is_worth(1, 1.0));
+
+        for _ in 0..8 {
+            values.record(1, 1);
+            values.record(9, 19);
+        }
+        values.record(11, 100);
+        assert_eq!(values.estimate(1), Some(1.0));
+        assert_eq!(values.estimate(9), Some(19.0));
+        assert_eq!(values.mean(), Some(10.0));
+        assert_eq!(values.summary(), "1:1.0,9:19.0");
+
+        assert!(values.is_worth(1, 0.0));
+        assert!(!values.is_worth(1, 0.5));
+        assert!(values.is_worth(9, 1.0));
+        // depth 2 is still unknown
+        assert!(values.is_worth(2, 1.0));
+    }
+}
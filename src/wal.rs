@@ -0,0 +1,162 @@
+use crate::journal::Journal;
+use crate::model::{MoneyList, Treasure};
+use std::collections::{BTreeMap, BTreeSet};
+use surf::http::convert::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum WalEvent {
+    // a dug treasure handed to the accountants
+    Found {
+        id: String,
+        depth: u64,
+        #[serde(default)]
+        pos_x: u64,
+        #[serde(default)]
+        pos_y: u64,
+    },
+    Cashed { id: String, coins: MoneyList },
+    // the server doesn't know the treasure, it is never cashed
+    Lost { id: String },
+    // coins paid for a license or refused by the server
+    Spent { coins: MoneyList },
+    // coins we hold, written when the log is compacted
+    Coins { coins: MoneyList },
+}
+
+// what the log says was left when the process stopped
+pub struct Recovered {
+    pub treasures: Vec<Treasure>,
+    pub coins: MoneyList,
+}
+
+// log of the treasures and coins the bot holds
+pub struct Wal {
+    journal: Journal<WalEvent>,
+}
+
+impl Wal {
+    // replays the log, rewrites it with only the recovered state and opens it
+    pub fn open(path: &str) -> Result<(Wal, Recovered), std::io::Error> {
+        let (journal, recovered) = Journal::open(path, recover, compact)?;
+        Ok((Wal { journal }, recovered))
+    }
+
+    pub fn append(&self, event: &WalEvent) {
+        self.journal.append(event)
+    }
+}
+
+fn recover(events: Vec<WalEvent>) -> Recovered {
+    let mut treasures: BTreeMap<String, Treasure> = BTreeMap::new();
+    let mut coins: BTreeSet<u32> = BTreeSet::new();
+    for event in events {
+        match event {
+            WalEvent::Found {
+                id,
+                depth,
+                pos_x,
+                pos_y,
+            } => {
+                let treasure = Treasure {
+                    id: id.clone(),
+                    depth,
+                    pos_x,
+                    pos_y,
+                };
+                treasures.insert(id, treasure);
+            }
+            WalEvent::Cashed { id, coins: cashed } => {
+                treasures.remove(&id);
+                coins.extend(cashed.iter());
+            }
+            WalEvent::Lost { id } => {
+                treasures.remove(&id);
+            }
+            WalEvent::Spent { coins: spent } => {
+                for coin in spent.iter() {
+                    coins.remove(coin);
+                }
+            }
+            WalEvent::Coins { coins: held } => coins.extend(held.iter()),
+        }
+    }
+    Recovered {
+        treasures: treasures.into_values().collect(),
+        coins: coins.iter().collect(),
+    }
+}
+
+fn compact(recovered: &Recovered) -> Vec<WalEvent> {
+    let mut events: Vec<WalEvent> = recovered
+        .treasures
+        .iter()
+        .map(|treasure| WalEvent::Found {
+            id: treasure.id.clone(),
+            depth: treasure.depth,
+            pos_x: treasure.pos_x,
+            pos_y: treasure.pos_y,
+        })
+        .collect();
+    if recovered.coins.len() > 0 {
+        events.push(WalEvent::Coins {
+            coins: recovered.coins.clone(),
+        });
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::MoneyList;
+    use crate::wal::{compact, recover, Recovered, WalEvent};
+
+    fn coins(c: &[u32]) -> MoneyList {
+        c.iter().collect()
+    }
+
+    fn found(id: &str, depth: u64) -> WalEvent {
+        WalEvent::Found {
+            id: id.to_string(),
+            depth,
+            pos_x: 0,
+            pos_y: depth,
+        }
+    }
+
+    fn check(recovered: &Recovered) {
+        let treasures: Vec<(String, u64, u64)> = recovered
+            .treasures
+            .iter()
+            .map(|t| (t.id.clone(), t.depth, t.pos_y))
+            .collect();
+        assert_eq!(treasures, vec![("c".to_string(), 3, 3)]);
+        assert_eq!(recovered.coins.iter().collect::<Vec<_>>(), vec![&1, &3, &5]);
+    }
+
+    #[test]
+    fn test_wal_recovery() {
+        let recovered = recover(vec![
+            found("a", 1),
+            found("b", 2),
+            found("c", 3),
+            WalEvent::Cashed {
+                id: "a".to_string(),
+                coins: coins(&[1, 2]),
+            },
+            WalEvent::Lost {
+                id: "b".to_string(),
+            },
+            WalEvent::Cashed {
+                id: "d".to_string(),
+                coins: coins(&[3, 4, 5]),
+            },
+            WalEvent::Spent {
+                coins: coins(&[2, 4]),
+            },
+        ]);
+        check(&recovered);
+        // the compacted log recovers the same state
+        check(&recover(compact(&recovered)));
+    }
+}
@@ -0,0 +1,127 @@
+use crate::model::MoneyList;
+use std::fmt::{Display, Formatter};
+use std::sync::Mutex;
+
+// coins set aside for one license request, handed back to the wallet with
+// `commit` once the server took them or `rollback` when it didn't
+pub struct Reservation {
+    coins: MoneyList,
+}
+
+impl Reservation {
+    pub fn coins(&self) -> &MoneyList {
+        &self.coins
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct WalletBalance {
+    pub available: usize,
+    pub reserved: usize,
+    pub deposited: u64,
+    pub spent: u64,
+}
+
+impl WalletBalance {
+    pub fn total(&self) -> usize {
+        self.available + self.reserved
+    }
+}
+
+impl Display for WalletBalance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}(+{})", self.available, self.reserved)
+    }
+}
+
+struct Purse {
+    coins: MoneyList,
+    balance: WalletBalance,
+}
+
+// every coin the bot holds, cashed treasures are deposited here and
+// attorneys pay for licenses out of it
+pub struct Wallet {
+    purse: Mutex<Purse>,
+}
+
+impl Wallet {
+    pub fn new() -> Wallet {
+        Wallet {
+            purse: Mutex::new(Purse {
+                coins: MoneyList::new(),
+                balance: WalletBalance::default(),
+            }),
+        }
+    }
+
+    pub fn deposit(&self, coins: MoneyList) {
+        let mut purse = self.purse.lock().unwrap();
+        purse.balance.available += coins.len();
+        purse.balance.deposited += coins.len() as u64;
+        purse.coins.extend(coins);
+    }
+
+    // sets aside `price` coins, the reservation is empty when the wallet
+    // holds fewer
+    pub fn reserve(&self, price: usize) -> Reservation {
+        let mut purse = self.purse.lock().unwrap();
+        if purse.coins.len() < price {
+            return Reservation {
+                coins: MoneyList::new(),
+            };
+        }
+        let coins = purse.coins.split_first(price);
+        purse.balance.available -= coins.len();
+        purse.balance.reserved += coins.len();
+        Reservation { coins }
+    }
+
+    // the server took the coins
+    pub fn commit(&self, reservation: Reservation) {
+        let mut purse = self.purse.lock().unwrap();
+        purse.balance.reserved -= reservation.coins.len();
+        purse.balance.spent += reservation.coins.len() as u64;
+    }
+
+    // the request failed before the server took the coins, they are ours again
+    pub fn rollback(&self, reservation: Reservation) {
+        let mut purse = self.purse.lock().unwrap();
+        purse.balance.reserved -= reservation.coins.len();
+        purse.balance.available += reservation.coins.len();
+        purse.coins.extend(reservation.coins);
+    }
+
+    pub fn balance(&self) -> WalletBalance {
+        self.purse.lock().unwrap().balance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::wallet::Wallet;
+
+    #[test]
+    fn test_wallet() {
+        let wallet = Wallet::new();
+        // fragments are paid for together
+        wallet.deposit([1, 2, 3].iter().collect());
+        wallet.deposit([4, 5, 6, 7].iter().collect());
+        assert_eq!(wallet.balance().available, 7);
+
+        let reservation = wallet.reserve(6);
+        assert_eq!(reservation.coins().len(), 6);
+        assert_eq!(wallet.balance().to_string(), "1(+6)");
+        assert_eq!(wallet.reserve(6).coins().len(), 0);
+        let other = wallet.reserve(1);
+        assert_eq!(other.coins().iter().collect::<Vec<_>>(), vec![&7]);
+
+        wallet.rollback(reservation);
+        wallet.commit(other);
+        let balance = wallet.balance();
+        assert_eq!((balance.available, balance.reserved), (6, 0));
+        assert_eq!((balance.deposited, balance.spent), (7, 1));
+        assert_eq!(wallet.reserve(0).coins().len(), 0);
+        assert_eq!(wallet.reserve(5).coins().iter().collect::<Vec<_>>(), vec![&1, &2, &3, &4, &5]);
+    }
+}
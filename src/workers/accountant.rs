@@ -1,59 +1,168 @@
-use crate::context::{Metrics, SyncContext};
-use crate::http::http_post;
+use crate::client::Game;
+use crate::context::{GameStage, SyncContext};
+use crate::http::ApiError;
 use crate::model::MoneyList;
+use crate::wal::WalEvent;
+use async_std::task;
+use std::sync::Arc;
 use std::time::Duration;
-use url::Url;
+
+// a treasure the client couldn't cash waits this long before it is queued again
+const REQUEUE_BACKOFF: Duration = Duration::from_millis(100);
 
 pub struct Accountant {
-    url: Url,
-    timeout: Duration,
-    client: surf::Client,
+    game: Arc<dyn Game>,
     sync: SyncContext,
 }
 
 impl Accountant {
-    pub fn new(url: Url, timeout: Duration, sync: SyncContext) -> Accountant {
-        Accountant {
-            url,
-            timeout,
-            client: surf::Client::new(),
-            sync,
-        }
+    pub fn new(game: Arc<dyn Game>, sync: SyncContext) -> Accountant {
+        Accountant { game, sync }
     }
     pub async fn start(self) {
         loop {
-            let treasure = self.sync.treasure_receiver.recv().await.unwrap();
-            self.sync.accountant_rate_limiter().await;
-            let money: MoneyList = match http_post(
-                &self.url,
-                self.timeout,
-                treasure.clone(),
-                &self.client,
-                self.sync.clone(),
-            )
-            .await
-            {
+            let treasure = self.sync.treasures.pop().await;
+            if self.sync.stage() == GameStage::Over {
+                self.sync.treasures.push(treasure).await;
+                return;
+            }
+            let money: MoneyList = match self.game.cash(treasure.id.clone()).await {
                 Ok(m) => m,
-                Err(_) => {
-                    self.sync.treasure_sender.send(treasure).await.unwrap();
-
-                    self.sync
-                        .metrics_sender
-                        .send(Metrics::new_cash(0u64, false))
-                        .await
-                        .unwrap();
+                Err(e) => {
+                    match e {
+                        // the server doesn't know the treasure, it can't be cashed
+                        ApiError::NoTreasure | ApiError::BadRequest(_) => {
+                            println!("cash error: {} {}", treasure.id, e);
+                            self.sync.log(WalEvent::Lost { id: treasure.id });
+                        }
+                        // out of attempts the treasure is queued again after a backoff,
+                        // once the game is over it is left in the queue for the summary
+                        ApiError::Transport(_)
+                        | ApiError::Timeout
+                        | ApiError::Decode(_)
+                        | ApiError::RateLimited
+                        | ApiError::ServerError(..)
+                        | ApiError::LicenseExhausted
+                        | ApiError::PaymentRequired
+                        | ApiError::TooManyLicenses => {
+                            if self.sync.stage() == GameStage::Over {
+                                self.sync.treasures.push(treasure).await
+                            } else {
+                                // the queue may fill up meanwhile, the accountant keeps cashing
+                                let treasures = self.sync.treasures.clone();
+                                task::spawn(async move {
+                                    task::sleep(REQUEUE_BACKOFF).await;
+                                    treasures.push(treasure).await
+                                });
+                            }
+                        }
+                    }
                     continue;
                 }
             };
 
             self.sync
-                .metrics_sender
-                .send(Metrics::new_cash(money.len() as u64, true))
-                .await
-                .unwrap();
+                .values
+                .write()
+                .unwrap()
+                .record(treasure.depth, money.len() as u64);
+            self.sync.log(WalEvent::Cashed {
+                id: treasure.id,
+                coins: money.clone(),
+            });
+            self.sync.wallet.deposit(money);
+        }
+    }
+}
 
+#[cfg(test)]
+mod tests {
+    use crate::client::Game;
+    use crate::config::Config;
+    use crate::context::SyncContext;
+    use crate::http::ApiError;
+    use crate::model::{Area, Balance, Dig, License, MoneyList, Tile, Treasure, TreasureList};
+    use crate::workers::accountant::Accountant;
+    use async_std::{future, task};
+    use async_trait::async_trait;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
 
-            self.sync.cash_sender.send(money).await.unwrap();
+    // cashes "gold" for 3 coins, doesn't know "lost", times out on "flaky"
+    struct FakeGame {
+        calls: Mutex<HashMap<String, u64>>,
+    }
+
+    #[async_trait]
+    impl Game for FakeGame {
+        async fn explore(&self, _: Area) -> Result<Tile, ApiError> {
+            unreachable!("accountant only cashes")
+        }
+        async fn dig(&self, _: Dig) -> Result<TreasureList, ApiError> {
+            unreachable!("accountant only cashes")
         }
+        async fn issue_license(&self, _: MoneyList) -> Result<License, ApiError> {
+            unreachable!("accountant only cashes")
+        }
+        async fn cash(&self, treasure: String) -> Result<MoneyList, ApiError> {
+            let calls = {
+                let mut calls = self.calls.lock().unwrap();
+                let count = calls.entry(treasure.clone()).or_default();
+                *count += 1;
+                *count
+            };
+            match (treasure.as_str(), calls) {
+                ("gold", _) => Ok([1, 2, 3].iter().collect()),
+                ("flaky", _) => Err(ApiError::Timeout),
+                _ => Err(ApiError::NoTreasure),
+            }
+        }
+        async fn balance(&self) -> Result<Balance, ApiError> {
+            unreachable!("accountant only cashes")
+        }
+        async fn health(&self) -> Result<(), ApiError> {
+            unreachable!("accountant only cashes")
+        }
+    }
+
+    #[async_std::test]
+    async fn test_accountant() {
+        let sync = SyncContext::new(Config::for_tests());
+        let game = Arc::new(FakeGame {
+            calls: Mutex::new(HashMap::new()),
+        });
+        let accountant = Accountant::new(game.clone(), sync.clone());
+        task::spawn(async move { accountant.start().await });
+
+        for id in ["lost", "flaky", "gold"] {
+            let treasure = Treasure {
+                id: id.to_string(),
+                depth: 2,
+                pos_x: 0,
+                pos_y: 0,
+            };
+            sync.treasures.push(treasure).await;
+        }
+        future::timeout(Duration::from_secs(1), async {
+            while sync.wallet.balance().available < 3 {
+                task::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(sync.wallet.balance().deposited, 3);
+
+        // a timeout reaching the accountant means the client ran out of attempts,
+        // the treasure is cashed again after a backoff
+        let flaky_calls = || game.calls.lock().unwrap()["flaky"];
+        future::timeout(Duration::from_secs(1), async {
+            while flaky_calls() < 2 {
+                task::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(game.calls.lock().unwrap()["lost"], 1);
     }
 }
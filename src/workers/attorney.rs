@@ -1,38 +1,29 @@
-use crate::context::{Metrics, SyncContext};
-use crate::http::http_post;
-use crate::model::{License, MoneyList};
+use crate::client::Game;
+use crate::context::SyncContext;
+use crate::http::ApiError;
+use crate::model::MoneyList;
+use crate::wal::WalEvent;
+use crate::wallet::Reservation;
 use rand::distributions::{Distribution, Uniform};
 use rand::rngs::StdRng;
 use rand::SeedableRng;
-use std::time::Duration;
-use url::Url;
+use std::sync::Arc;
 
 pub struct Attorney {
-    url: Url,
-    timeout: Duration,
-    license_min_cost: u64,
-    license_max_cost: u64,
+    game: Arc<dyn Game>,
     free_license_probability: u64,
-    client: surf::Client,
     sync: SyncContext,
 }
 
 impl Attorney {
     pub fn new(
-        url: Url,
-        timeout: Duration,
-        license_min_cost: u64,
-        license_max_cost: u64,
+        game: Arc<dyn Game>,
         free_license_probability: u64,
         sync: SyncContext,
     ) -> Attorney {
         Attorney {
-            url,
-            timeout,
-            license_min_cost,
-            license_max_cost,
+            game,
             free_license_probability,
-            client: surf::Client::new(),
             sync,
         }
     }
@@ -40,81 +31,78 @@ impl Attorney {
         let mut rng = StdRng::from_entropy();
         let between = Uniform::from(0..100);
         loop {
-            let mut license = self.sync.empty_license_receiver.recv().await.unwrap();
-            let payload: MoneyList;
-            if self.free_license_probability <= 0
+            self.sync.licenses.reserve().await;
+            if !self.sync.is_running() {
+                self.sync.licenses.release().await;
+                return;
+            }
+            let reservation = if self.free_license_probability == 0
                 || (self.free_license_probability < 100
                     && between.sample(&mut rng) > self.free_license_probability)
             {
-                payload = match self.sync.cash_receiver.try_recv() {
-                    Ok(cash) => {
-                        if cash.len() == 0 {
-                            cash
-                        } else {
-                            let split = cash.get_optimal_list(
-                                self.license_max_cost as usize,
-                                self.license_min_cost as usize,
-                            );
-                            if split.exchange.len() > 0 {
-                                self.sync.cash_sender.send(split.exchange).await.unwrap();
-                            }
-                            split.money
-                        }
-                    }
-                    Err(_) => MoneyList::new(),
-                };
+                let price = self.sync.pricing.read().unwrap().choose(
+                    self.sync.wallet.balance().available,
+                    self.sync.dig_queue.load(),
+                );
+                Some(self.sync.wallet.reserve(price))
             } else {
-                payload = MoneyList::new();
-            }
-            let mut good_license = true;
-            loop {
-                self.sync.attorney_rate_limiter().await;
-                license = match http_post(
-                    &self.url,
-                    self.timeout,
-                    payload.clone(),
-                    &self.client,
-                    self.sync.clone(),
-                )
-                .await
-                {
-                    Ok(l) => l,
-                    Err(e) => {
-
-                        let metric = Metrics::new_license(0, payload.len() as u64);
-
-                        if e.status == 402 {
-                            self.sync
-                                .empty_license_sender
-                                .send(License::new())
-                                .await
-                                .unwrap();
-                            good_license = false;
-                            break;
-                        }
-
-                        self.sync.metrics_sender.send(metric).await.unwrap();
-
-
-                        //self.sync.empty_license_sender.send(license).await.unwrap();
-                        //self.sync.cash_sender.send(payload).await.unwrap();
-                        continue;
+                None
+            };
+            let payload = match &reservation {
+                Some(r) => r.coins().clone(),
+                None => MoneyList::new(),
+            };
+            match self.game.issue_license(payload).await {
+                Ok(license) => {
+                    let price = reservation.as_ref().map_or(0, |r| r.coins().len());
+                    self.sync
+                        .pricing
+                        .write()
+                        .unwrap()
+                        .record(price, license.dig_allowed);
+                    self.spend(reservation);
+                    self.sync.licenses.issue(license).await
+                }
+                // over the active license limit, the slot waits for a license to retire
+                Err(ApiError::TooManyLicenses) => {
+                    if let Some(reservation) = reservation {
+                        self.sync.wallet.rollback(reservation);
                     }
-                };
-                break;
-            }
-            if good_license {
-
-                let metric = Metrics::new_license(license.dig_allowed, payload.len() as u64);
-                match self.sync.license_sender.send(license).await {
-                    Ok(_) => {
-
-                        self.sync.metrics_sender.send(metric).await.unwrap();
-
+                    self.sync.licenses.reject().await;
+                }
+                // the coins won't buy a license however often we ask
+                Err(ApiError::PaymentRequired | ApiError::BadRequest(_)) => {
+                    self.sync.licenses.release().await;
+                    self.spend(reservation)
+                }
+                // the client ran out of attempts or the game stopped running,
+                // the coins are probably still ours
+                Err(
+                    ApiError::Transport(_)
+                    | ApiError::Timeout
+                    | ApiError::Decode(_)
+                    | ApiError::RateLimited
+                    | ApiError::ServerError(..)
+                    | ApiError::NoTreasure
+                    | ApiError::LicenseExhausted,
+                ) => {
+                    self.sync.licenses.release().await;
+                    if let Some(reservation) = reservation {
+                        self.sync.wallet.rollback(reservation);
                     }
-                    Err(e) => println!("attorney error - {}", e.to_string()),
                 }
             }
         }
     }
+
+    fn spend(&self, reservation: Option<Reservation>) {
+        if let Some(reservation) = reservation {
+            if reservation.coins().len() > 0 {
+                self.sync.log(WalEvent::Spent {
+                    coins: reservation.coins().clone(),
+                });
+            }
+            self.sync.wallet.commit(reservation);
+        }
+    }
 }
@@ -0,0 +1,99 @@
+use crate::client::Game;
+use crate::context::{Metrics, SyncContext};
+use crate::http::ApiError;
+use crate::model::MoneyList;
+use async_std::task;
+use std::cmp::min;
+use std::time::{Duration, Instant};
+use std::sync::Arc;
+
+const RESEED_BACKOFF: Duration = Duration::from_millis(500);
+
+pub struct Banker {
+    game: Arc<dyn Game>,
+    period: Duration,
+    reseed: bool,
+    reseed_deadline: Duration,
+    sync: SyncContext,
+}
+
+impl Banker {
+    pub fn new(
+        game: Arc<dyn Game>,
+        period: Duration,
+        reseed: bool,
+        reseed_deadline: Duration,
+        sync: SyncContext,
+    ) -> Banker {
+        Banker {
+            game,
+            period,
+            reseed,
+            reseed_deadline,
+            sync,
+        }
+    }
+
+    // deposits the coins the server holds for us back into the wallet,
+    // meant to be called once before the attorneys start after a restart.
+    // returns the last error once the deadline has passed
+    pub async fn reseed(&self) -> Result<(), ApiError> {
+        if !self.reseed {
+            return Ok(());
+        }
+        let start = Instant::now();
+        let balance = loop {
+            match self.game.balance().await {
+                Ok(b) => break b,
+                Err(e) => {
+                    println!("banker reseed error: {}", e);
+                    if start.elapsed() >= self.reseed_deadline {
+                        return Err(e);
+                    }
+                }
+            }
+            task::sleep(min(
+                RESEED_BACKOFF,
+                self.reseed_deadline.saturating_sub(start.elapsed()),
+            ))
+            .await;
+        };
+        let coins: MoneyList = balance.wallet.iter().collect();
+        println!("banker reseed: {}", balance);
+        self.sync
+            .metrics_sender
+            .send(Metrics::new_coins_reseeded(coins.len() as u64))
+            .await
+            .unwrap();
+        self.sync.wallet.deposit(coins);
+        Ok(())
+    }
+
+    // drift > 0 - the server holds coins we lost track of,
+    // drift < 0 - we count coins the server has already spent.
+    // in-flight cash and license requests show up as short-lived drift
+    pub async fn start(self) {
+        let mut server = 0i64;
+        let mut drift = 0i64;
+        loop {
+            task::sleep(self.period).await;
+            let balance = match self.game.balance().await {
+                Ok(b) => b,
+                Err(e) => {
+                    println!("banker error: {}", e);
+                    continue;
+                }
+            };
+            let local = self.sync.wallet.balance().total() as i64;
+            let new_server = balance.balance as i64;
+            let new_drift = new_server - local;
+            self.sync
+                .metrics_sender
+                .send(Metrics::new_balance(new_server - server, new_drift - drift))
+                .await
+                .unwrap();
+            server = new_server;
+            drift = new_drift;
+        }
+    }
+}
@@ -1,111 +1,172 @@
+use crate::checkpoint::CheckpointEvent;
 use crate::context::{Metrics, SyncContext};
-use crate::http::http_post;
-use crate::model::{Dig, TreasureList};
-use rand::distributions::{Distribution, Uniform};
-use rand::rngs::StdRng;
-use rand::SeedableRng;
+use crate::client::Game;
+use crate::http::ApiError;
+use crate::license::DigOutcome;
+use crate::model::{Area, Dig, Tile, Treasure, TreasureList};
+use crate::wal::WalEvent;
+use async_std::task;
+use std::sync::Arc;
 use std::time::Duration;
-use url::Url;
+
+// how long a point the client couldn't dig stays out of the dig queue
+const REQUEUE_BACKOFF: Duration = Duration::from_millis(100);
 
 pub struct Digger {
-    url: Url,
-    timeout: Duration,
-    client: surf::Client,
+    game: Arc<dyn Game>,
     sync: SyncContext,
     min_depth: u64,
     max_depth: u64,
-    min_depth_probability: u64,
+    points_in_flight: u64,
 }
 
 impl Digger {
     pub fn new(
-        url: Url,
-        timeout: Duration,
+        game: Arc<dyn Game>,
         min_depth: u64,
         max_depth: u64,
-        min_depth_probability: u64,
+        points_in_flight: u64,
         sync: SyncContext,
     ) -> Digger {
         Digger {
-            url,
-            timeout,
-            client: surf::Client::new(),
+            game,
             min_depth,
             max_depth,
+            points_in_flight: points_in_flight.max(1),
             sync,
-            min_depth_probability,
         }
     }
 
+    // digs `points_in_flight` points at once,
+    // the depths of every point are still dug one after another
     pub async fn start(self) {
-        let mut rng = StdRng::from_entropy();
-        let between = Uniform::from(0..100);
+        let digger = Arc::new(self);
+        let lanes: Vec<_> = (0..digger.points_in_flight)
+            .map(|_| {
+                let digger = digger.clone();
+                task::spawn(async move { digger.dig_points().await })
+            })
+            .collect();
+        for lane in lanes {
+            lane.await;
+        }
+    }
+
+    async fn dig_points(&self) {
         loop {
-            let tile = self.sync.tile_receiver.recv().await.unwrap();
+            if !self.sync.is_running() {
+                return;
+            }
+            let tile = self.sync.dig_queue.pop().await;
             let mut dig = Dig::from_tile(tile, 0);
+            {
+                let map = self.sync.map.read().unwrap();
+                if map.is_exhausted(dig.pos_x, dig.pos_y) {
+                    continue;
+                }
+                dig.depth = map.depth(dig.pos_x, dig.pos_y) + 1;
+            }
 
             while dig.amount > 0 && dig.depth <= self.max_depth {
-                let mut license = self.sync.license_receiver.recv().await.unwrap();
-                self.sync.digger_rate_limiter().await;
-                dig.license_id = license.id;
-
-                let treasures: TreasureList = match http_post(
-                    &self.url,
-                    self.timeout,
-                    dig,
-                    &self.client,
-                    self.sync.clone(),
-                )
-                .await
-                {
-                    Ok(t) => {
+                let permit = self.sync.licenses.acquire().await;
+                dig.license_id = permit.license_id;
 
-                        self.sync
-                            .metrics_sender
-                            .send(Metrics::new_dig(true, dig.depth))
-                            .await
-                            .unwrap();
-
-                        t
-                    }
+                let treasures: TreasureList = match self.game.dig(dig).await {
+                    Ok(t) => t,
                     Err(e) => {
-
-                        self.sync
-                            .metrics_sender
-                            .send(Metrics::new_dig(false, dig.depth))
-                            .await
-                            .unwrap();
-
-
-                        if e.status == 404  || e.status == 422{
-                            TreasureList::new()
-                        } else {
-                            // if e.status!=667 {
-                            //     println!("digger error: {}", e.to_string());
-                            // }
-                            self.sync.license_sender.send(license).await.unwrap();
-                            continue;
+                        match e {
+                            // the server took the dig but had nothing for us
+                            ApiError::NoTreasure | ApiError::BadRequest(_) => TreasureList::new(),
+                            // the server dug the level but what it found is unknown,
+                            // the level isn't recorded as empty
+                            ApiError::Decode(_) => {
+                                dig.depth += 1;
+                                self.sync.licenses.complete(permit, DigOutcome::Used).await;
+                                continue;
+                            }
+                            ApiError::LicenseExhausted => {
+                                self.sync.licenses.complete(permit, DigOutcome::Invalid).await;
+                                continue;
+                            }
+                            // out of attempts, the point is dug again later
+                            ApiError::Transport(_)
+                            | ApiError::Timeout
+                            | ApiError::RateLimited
+                            | ApiError::ServerError(..)
+                            | ApiError::PaymentRequired
+                            | ApiError::TooManyLicenses => {
+                                self.sync.licenses.complete(permit, DigOutcome::Unused).await;
+                                self.requeue(dig);
+                                break;
+                            }
                         }
                     }
                 };
+                let found = treasures.0.len() as u64;
                 dig.depth += 1;
-                license.dig_used += 1;
-                if license.dig_used == license.dig_allowed {
-                    self.sync.empty_license_sender.send(license).await.unwrap()
-                } else {
-                    self.sync.license_sender.send(license).await.unwrap()
-                }
-                for treasure in treasures.0.into_iter() {
+                self.sync.licenses.complete(permit, DigOutcome::Used).await;
+                for id in treasures.0.into_iter() {
                     dig.amount -= 1;
-                    if dig.depth - 1 >= self.min_depth
-                        && (dig.depth - 1 > self.min_depth
-                            || (self.min_depth_probability >= 100
-                                || between.sample(&mut rng) <= self.min_depth_probability))
-                    {
-                        self.sync.treasure_sender.send(treasure).await.unwrap();
+                    let treasure = Treasure {
+                        id,
+                        depth: dig.depth - 1,
+                        pos_x: dig.pos_x,
+                        pos_y: dig.pos_y,
+                    };
+                    if self.is_worth_cashing(treasure.depth) {
+                        self.sync.log(WalEvent::Found {
+                            id: treasure.id.clone(),
+                            depth: treasure.depth,
+                            pos_x: treasure.pos_x,
+                            pos_y: treasure.pos_y,
+                        });
+                        self.sync.treasures.push(treasure).await;
+                    } else {
+                        self.sync
+                            .metrics_sender
+                            .send(Metrics::new_treasure_dropped())
+                            .await
+                            .unwrap();
                     }
                 }
+                self.sync.map.write().unwrap().record_dig(
+                    dig.pos_x,
+                    dig.pos_y,
+                    dig.depth - 1,
+                    found,
+                    dig.amount == 0,
+                );
+                self.sync.save_progress(CheckpointEvent::Dug {
+                    pos_x: dig.pos_x,
+                    pos_y: dig.pos_y,
+                    depth: dig.depth - 1,
+                    left: dig.amount,
+                });
             }
         }
     }
+
+    // pushes the rest of the point back from a separate task, the lane goes
+    // on popping so a full queue can't block every lane
+    fn requeue(&self, dig: Dig) {
+        let dig_queue = self.sync.dig_queue.clone();
+        task::spawn(async move {
+            task::sleep(REQUEUE_BACKOFF).await;
+            let tile = Tile {
+                amount: dig.amount,
+                area: Area::new(dig.pos_x, dig.pos_y, 1, 1),
+            };
+            dig_queue.push(tile, dig.depth - 1).await
+        });
+    }
+
+    // treasures deeper than min depth are always cashed, the ones at min depth
+    // and shallower only while their learned value pays for the cash request
+    fn is_worth_cashing(&self, depth: u64) -> bool {
+        if depth > self.min_depth {
+            return true;
+        }
+        let load = self.sync.treasures.load();
+        self.sync.values.read().unwrap().is_worth(depth, load)
+    }
 }
@@ -1,160 +1,127 @@
+use crate::checkpoint::CheckpointEvent;
+use crate::client::Game;
 use crate::context::{Metrics, SyncContext};
-use crate::http::http_post;
+use crate::http::ApiError;
 use crate::model::Tile;
-use async_recursion::async_recursion;
-use std::time::Duration;
-use url::Url;
+use crate::search::{Prober, SearchStrategy};
+use async_trait::async_trait;
+use std::sync::Arc;
 
 pub struct Explorer {
-    url: Url,
-    timeout: Duration,
-    client: surf::Client,
-    min_amount: u64,
-    binary_enabled: bool,
-    flat_threshold: u64,
-    flat_size: u64,
+    game: Arc<dyn Game>,
+    strategy: Arc<dyn SearchStrategy>,
     sync: SyncContext,
 }
 
 impl Explorer {
     pub fn new(
-        url: Url,
-        timeout: Duration,
-        min_amount: u64,
-        binary_enabled: bool,
-        flat_threshold: u64,
-        flat_size: u64,
+        game: Arc<dyn Game>,
+        strategy: Arc<dyn SearchStrategy>,
         sync: SyncContext,
     ) -> Explorer {
         Explorer {
-            url,
-            timeout,
-            client: surf::Client::new(),
-            min_amount,
-            binary_enabled,
-            flat_threshold,
-            flat_size,
+            game,
+            strategy,
             sync,
         }
     }
 
-    pub async fn check_tile(&self, tile: Tile) -> Tile {
-        let result: Tile;
-        loop {
-            self.sync.explore_rate_limiter().await;
-            //println!("{}",tile.area.size_x);
-            result = match http_post(
-                &self.url,
-                self.timeout,
-                tile.area.clone(),
-                &self.client,
-                self.sync.clone(),
-            )
-            .await
-            {
-                Ok(t) => t,
-                Err(_) => {
-                    continue;
-                }
-            };
-
+    // the tile with its amount, None if it couldn't be explored
+    pub async fn check_tile(&self, tile: Tile) -> Option<Tile> {
+        let known = self.sync.map.read().unwrap().known(&tile.area);
+        if let Some(amount) = known {
             self.sync
                 .metrics_sender
-                .send(Metrics::new_explore(
-                    result.is_single_point() && result.amount > 0,
-                    result.area.pos_x,
-                    result.area.pos_y,
-                    result.area.size_x,
-                    result.area.size_y,
-                ))
+                .send(Metrics::new_known_explore())
                 .await
                 .unwrap();
-
-            break;
+            return Some(Tile {
+                amount,
+                area: tile.area,
+            });
         }
-        result
-    }
-
-    #[async_recursion]
-    pub async fn search(&self, tile: Tile, mut top: bool) -> Vec<Tile> {
-        let mut result = Vec::new();
-        let mut tile = tile;
-        if tile.amount == 0 {
-            tile = self.check_tile(tile).await;
+        if !self.sync.is_running() {
+            return None;
         }
-        let mut min_amount = 1;
-        if top {
-            min_amount = self.min_amount;
-            top = false
-        }
-        if tile.has_treasures(min_amount) {
-            if tile.is_single_point() {
-                result.push(tile);
-                return result;
+        match self.game.explore(tile.area.clone()).await {
+            Ok(result) => {
+                self.sync
+                    .map
+                    .write()
+                    .unwrap()
+                    .record_explore(&result.area, result.amount);
+                Some(result)
             }
-            if tile.area.size_x > self.flat_threshold && self.binary_enabled {
-                    let (left, mut right) = tile.split();
-                    let mut left = self.search(left, top).await;
-                    left.iter().for_each(|t| tile.amount -= t.amount);
-                    result.append(&mut left);
-                    if tile.amount > 0 {
-                        right.amount = tile.amount;
-                        let mut right = self.search(right, top).await;
-                        result.append(&mut right);
-                    }
-            } else {
-                let tile_size = if tile.area.size_x <= self.flat_size {
-                    1
-                } else if !self.binary_enabled && tile.area.size_x>self.flat_threshold{
-                    self.flat_threshold
-                } else {
-                    self.flat_size
-                };
-
-                let iter = tile.split_to_tiles(tile_size).into_iter();
-                let len = iter.len();
-                let mut cur = 1;
-
-                for mut t in iter {
-                    if cur == len {
-                        t.amount = tile.amount;
-
-                        if tile_size == 1 {
-                            self.sync
-                                .metrics_sender
-                                .send(Metrics::new_calculated_explore(
-                                    true,
-                                    t.area.pos_x,
-                                    t.area.pos_y,
-                                ))
-                                .await
-                                .unwrap();
-                        }
-
-
-                        result.push(t);
-                        break;
-                    }
-                    let mut t = self.search(t, top).await;
-                    t.iter().for_each(|t| tile.amount -= t.amount);
-                    result.append(&mut t);
-                    if tile.amount == 0 {
-                        break;
-                    }
-                    cur += 1;
+            Err(e) => match e {
+                // the client ran out of attempts or the game stopped running
+                ApiError::Transport(_)
+                | ApiError::Timeout
+                | ApiError::Decode(_)
+                | ApiError::RateLimited
+                | ApiError::ServerError(..)
+                | ApiError::TooManyLicenses => None,
+                // the area itself is wrong, asking again won't help
+                ApiError::BadRequest(_)
+                | ApiError::NoTreasure
+                | ApiError::LicenseExhausted
+                | ApiError::PaymentRequired => {
+                    println!("explore error: {} {}", tile.area, e);
+                    None
                 }
-            }
+            },
         }
-        result
     }
 
     pub async fn start(self) {
         loop {
             let initial_area = self.sync.area_receiver.recv().await.unwrap();
-            let tiles = self.search(initial_area, true).await;
-            for tile in tiles {
+            if !self.sync.is_running() {
+                return;
+            }
+            let seed = initial_area.area.clone();
+            let found = self.strategy.search(initial_area, true, &self).await;
+            let failed = found.failed;
+            for tile in found.tiles {
+                self.sync.save_progress(CheckpointEvent::Found {
+                    tile: Tile {
+                        amount: tile.amount,
+                        area: tile.area.clone(),
+                    },
+                });
                 self.sync.tile_sender.send(tile).await.unwrap();
             }
+            // a failed explore, the drain included, leaves the seed unsearched
+            if !failed {
+                self.sync
+                    .save_progress(CheckpointEvent::Searched { area: seed });
+            }
         }
     }
 }
+
+#[async_trait]
+impl Prober for Explorer {
+    async fn explore(&self, tile: Tile) -> Option<Tile> {
+        self.check_tile(tile).await
+    }
+
+    async fn report_calculated(&self, tile: &Tile) {
+        self.sync
+            .metrics_sender
+            .send(Metrics::new_calculated_explore(
+                true,
+                tile.area.pos_x,
+                tile.area.pos_y,
+            ))
+            .await
+            .unwrap();
+    }
+
+    async fn report_plan(&self, expected: f32, actual: f32, skipped: bool) {
+        self.sync
+            .metrics_sender
+            .send(Metrics::new_explore_plan(expected, actual, skipped))
+            .await
+            .unwrap();
+    }
+}
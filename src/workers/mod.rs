@@ -1,5 +1,7 @@
 pub mod accountant;
 pub mod attorney;
+pub mod banker;
 pub mod explorer;
 pub mod digger;
 pub mod statist;
+pub mod watchman;
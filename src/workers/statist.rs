@@ -14,6 +14,33 @@ impl Statist {
             sync,
         }
     }
+    pub async fn summary(&self, elapsed: Duration) {
+        while !self.sync.metrics_receiver.is_empty() {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+        let hm: Metrics = *self.sync.metrics.lock().unwrap();
+        println!(
+            "game over after {}s: explored={}(found={},price={:.0}),dug={}(found={}),cashed={}({} coins),licenses={}({} coins,409={})|left: areas={},tiles={},treasures={}(~{:.0} coins),coins={}|http={}(Х={})",
+            elapsed.as_secs(),
+            hm.explore_count,
+            hm.explore_success,
+            hm.explore_price,
+            hm.dig_count,
+            hm.dig_success,
+            hm.cash_success,
+            hm.cash_value,
+            hm.license_count,
+            hm.coins_spent,
+            hm.license_rejected,
+            self.sync.area_receiver.len(),
+            self.sync.dig_queue.len(),
+            self.sync.treasures.len(),
+            self.sync.treasures.uncashed_value(),
+            self.sync.wallet.balance(),
+            hm.sum_http(),
+            hm.http_other,
+        );
+    }
     pub async fn start(self) {
         let start = Instant::now();
         let http_metrics = self.sync.metrics.clone();
@@ -21,20 +48,22 @@ impl Statist {
         task::spawn(async move {
             loop {
                 let m = http_metrics_receiver.recv().await.unwrap();
-                drop(http_metrics.lock().unwrap().add(m));
+                http_metrics.lock().unwrap().add(m);
             }
         });
         let mut old_metrics = Metrics::new();
         loop {
             let hm: Metrics = *self.sync.metrics.lock().unwrap();
+            let licenses = self.sync.licenses.stats();
             println!(
-                "{}({}): a={},tl={},l={},tr={}|409={},422={},429={},5x={},Х={}|r:{}|e={}|l={}|d={}|c={}|cs={}|er={:.3}|pc={:.3}|pps={:.3}|tf={},tc={}({}),avg_t={:.3}|price:[pps:{:.0}({:.0}+{:.0}+{:.0}),e={:.0},d={:.0},c={:.0}],{}-{}={}",
+                "{}({}): a={},tl={},l={},tr={}(uv={:.0})|409={},422={},429={},5x={},Х={}|r:{}|e={}|l={}|d={}|c={}|cs={}|er={:.3}|pc={:.3}|pps={:.3}|tf={},tc={}({}),avg_t={:.3}|price:[pps:{:.0}({:.0}+{:.0}+{:.0}),e={:.0},d={:.0},c={:.0}],{}-{}={}|hc={}ms({})|w={}|bal={}({:+})|plan={:.0}/{:.0}(-{})|ek={}|dq=[{}]|td={},val=[{}]|lic={}/{}(+{},x{},-{},!{}),lp=[{}]|rt={}(dl={})|cb=[{}]",
                 start.elapsed().as_secs(),
                 self.sync.is_phase2(),
                 self.sync.area_receiver.len(),
-                self.sync.tile_receiver.len(),
-                self.sync.license_receiver.len(),
-                self.sync.treasure_receiver.len(),
+                self.sync.dig_queue.len(),
+                licenses.permits,
+                self.sync.treasures.len(),
+                self.sync.treasures.uncashed_value(),
                 hm.http409,
                 hm.http422,
                 hm.http429,
@@ -52,11 +81,11 @@ impl Statist {
                 },
                 match hm.explore_count {
                     0 => 0.0,
-                    _ => hm.explore_price as f32/hm.explore_count as f32
+                    _ => hm.explore_price/hm.explore_count as f32
                 },
                 match hm.explore_count {
                     0 => 0.0,
-                    _ => hm.explore_price as f32/hm.explore_success as f32
+                    _ => hm.explore_price/hm.explore_success as f32
                 },
                 hm.dig_success,
                 hm.cash_success,
@@ -74,7 +103,29 @@ impl Statist {
                 hm.cash_price,
                 hm.cash_value,
                 hm.license_price,
-                 hm.cash_value - hm.license_price,
+                 hm.cash_value as i64 - hm.license_price as i64,
+                hm.health_check_wait_ms,
+                hm.health_check_count,
+                self.sync.wallet.balance(),
+                hm.wallet_server,
+                hm.wallet_drift,
+                hm.explore_plan_expected,
+                hm.explore_plan_actual,
+                hm.explore_plan_skipped,
+                hm.explore_known,
+                self.sync.dig_queue.distribution(),
+                hm.treasure_dropped,
+                self.sync.values.read().unwrap().summary(),
+                licenses.active,
+                licenses.limit,
+                licenses.issued,
+                licenses.exhausted,
+                licenses.retired,
+                licenses.rejected,
+                self.sync.pricing.read().unwrap().summary(),
+                hm.retry_count,
+                hm.retry_exhausted,
+                self.sync.breakers.summary(),
             );
             old_metrics = hm;
 
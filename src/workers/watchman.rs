@@ -0,0 +1,66 @@
+use crate::client::Game;
+use crate::context::{Metrics, SyncContext};
+use crate::http::ApiError;
+use async_std::task;
+use std::cmp::min;
+use std::time::{Duration, Instant};
+use std::sync::Arc;
+
+pub struct Watchman {
+    game: Arc<dyn Game>,
+    backoff: Duration,
+    max_backoff: Duration,
+    deadline: Duration,
+    sync: SyncContext,
+}
+
+impl Watchman {
+    pub fn new(
+        game: Arc<dyn Game>,
+        backoff: Duration,
+        max_backoff: Duration,
+        deadline: Duration,
+        sync: SyncContext,
+    ) -> Watchman {
+        Watchman {
+            game,
+            backoff,
+            max_backoff,
+            deadline,
+            sync,
+        }
+    }
+
+    // polls health-check until the server answers, returns the time spent waiting
+    // or the last error once the deadline has passed
+    pub async fn wait(self) -> Result<Duration, ApiError> {
+        let start = Instant::now();
+        let mut backoff = self.backoff;
+        let mut attempts = 0u64;
+        if self.sync.replayer.is_none() {
+            loop {
+                attempts += 1;
+                match self.game.health().await {
+                    Ok(_) => break,
+                    Err(e) => {
+                        if attempts == 1 {
+                            println!("waiting for server: {}", e);
+                        }
+                        if start.elapsed() >= self.deadline {
+                            return Err(e);
+                        }
+                    }
+                }
+                task::sleep(min(backoff, self.deadline.saturating_sub(start.elapsed()))).await;
+                backoff = min(backoff * 2, self.max_backoff);
+            }
+        }
+        let waited = start.elapsed();
+        self.sync
+            .metrics_sender
+            .send(Metrics::new_health_check(waited, attempts))
+            .await
+            .unwrap();
+        Ok(waited)
+    }
+}